@@ -10,6 +10,43 @@ impl Arrow {
     Self(bits)
   }
 
+  /// Parses the five-column form printed by `Debug`, such as `--*>>`.
+  pub fn parse(src: &str) -> Option<Self> {
+    let src = src.as_bytes();
+    if src.len() != 5 {
+      return None;
+    }
+    let mut bits = 0;
+    for (i, (&c, glyph)) in src.iter().zip(b"<<*>>").enumerate() {
+      match c {
+        b'-' => {}
+        c if c == *glyph => bits |= 0b10000 >> i,
+        _ => return None,
+      }
+    }
+    Some(Self::from_bits(bits))
+  }
+
+  /// Parses either the five-column form, or atoms joined by `|`, such as
+  /// `*|>>`.
+  pub fn parse_glyphs(src: &str) -> Option<Self> {
+    if let Some(arrow) = Self::parse(src) {
+      return Some(arrow);
+    }
+    let mut bits = 0;
+    for atom in src.split('|') {
+      bits |= match atom {
+        "<<" => 0b10000,
+        "<" => 0b01000,
+        "*" => 0b00100,
+        ">" => 0b00010,
+        ">>" => 0b00001,
+        _ => return None,
+      };
+    }
+    Some(Self::from_bits(bits))
+  }
+
   pub fn join(self, other: Self) -> Option<Self> {
     if self.0 == 0 || other.0 == 0 {
       return None;
@@ -35,14 +72,14 @@ impl Arrow {
     for (a, b, c) in table {
       let b = Arrow(b).converse().unwrap().0;
       if (self.0 & a) != 0 && (other.0 & b) != 0 {
-        o |= c as u8
+        o |= c
       }
       let a = Arrow(a).converse().unwrap().0;
       let b = Arrow(b).converse().unwrap().0;
       let (a, b) = (b, a);
       let c = Arrow(c).converse().unwrap().0;
       if (self.0 & a) != 0 && (other.0 & b) != 0 {
-        o |= c as u8
+        o |= c
       }
     }
     if o == 0b11111 {
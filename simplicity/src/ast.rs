@@ -1,8 +1,8 @@
-use std::fmt::{self, Debug};
+use std::fmt::{self, Debug, Display};
 
 use util::new_idx;
 
-use crate::scope::Scope;
+use crate::{arrow::Arrow, scope::Scope};
 
 #[derive(Debug, Clone)]
 pub struct System {
@@ -11,9 +11,133 @@ pub struct System {
   pub nets: Vec<NetDef>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct AgentDef {
-  pub auxiliary: Vec<Vec<()>>,
+  /// The partition of the auxiliary ports, each port with its name if it has
+  /// one.
+  pub auxiliary: Vec<Partition<Option<String>>>,
+  /// Relations between ports that hold in every node of the agent, beyond
+  /// those given by the partition.
+  pub axioms: Vec<Axiom>,
+  /// The lints silenced for the agent.
+  pub allow: Vec<Lint>,
+}
+
+/// A relation between two ports of an agent, by their index among the ports of
+/// a node, so that the principal port is `0`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Axiom {
+  pub a: usize,
+  pub arrow: Arrow,
+  pub b: usize,
+}
+
+/// A part of a partition of ports: a port in a group of its own, or a group of
+/// finer parts, written in braces.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Partition<T> {
+  Port(T),
+  Group(Vec<Partition<T>>),
+}
+
+impl<T> Partition<T> {
+  /// The ports of the part, in order.
+  pub fn ports(&self) -> Vec<&T> {
+    match self {
+      Partition::Port(port) => vec![port],
+      Partition::Group(parts) => parts.iter().flat_map(Partition::ports).collect(),
+    }
+  }
+
+  /// The number of ports in the part.
+  pub fn count(&self) -> usize {
+    match self {
+      Partition::Port(_) => 1,
+      Partition::Group(parts) => parts.iter().map(Partition::count).sum(),
+    }
+  }
+
+  /// The same grouping of the ports that `f` gives in order.
+  pub fn map<U>(&self, f: &mut impl FnMut(&T) -> U) -> Partition<U> {
+    match self {
+      Partition::Port(port) => Partition::Port(f(port)),
+      Partition::Group(parts) => Partition::Group(parts.iter().map(|x| x.map(f)).collect()),
+    }
+  }
+
+  pub fn show(&self, f: &impl Fn(&T) -> String) -> String {
+    match self {
+      Partition::Port(port) => f(port),
+      Partition::Group(parts) => {
+        format!("{{{}}}", parts.iter().map(|x| x.show(f)).collect::<Vec<_>>().join(", "))
+      }
+    }
+  }
+}
+
+impl AgentDef {
+  pub fn arity(&self) -> usize {
+    1 + self.auxiliary.iter().map(Partition::count).sum::<usize>()
+  }
+
+  /// The partition of the auxiliary ports, without their names.
+  pub fn partition(&self) -> Vec<Partition<()>> {
+    self.auxiliary.iter().map(|x| x.map(&mut |_| ())).collect()
+  }
+
+  /// The same auxiliary ports, keeping their names, grouped as in `partition`.
+  /// Ports beyond those of `self` are unnamed.
+  pub fn regroup(&self, partition: &[Partition<()>]) -> AgentDef {
+    let mut names = self.names().into_iter();
+    let auxiliary = partition
+      .iter()
+      .map(|x| x.map(&mut |()| names.next().flatten().map(str::to_owned)))
+      .collect();
+    AgentDef { auxiliary, axioms: self.axioms.clone(), allow: self.allow.clone() }
+  }
+
+  /// The name of each auxiliary port, in order.
+  pub fn names(&self) -> Vec<Option<&str>> {
+    self.auxiliary.iter().flat_map(Partition::ports).map(Option::as_deref).collect()
+  }
+
+  /// The index, among the ports of a node, of the auxiliary port called
+  /// `name`.
+  pub fn port(&self, name: &str) -> Option<usize> {
+    self.names().iter().position(|&x| x == Some(name)).map(|i| i + 1)
+  }
+
+  /// Describes the `i`th port of a node, by its name if it has one.
+  pub fn describe_port(&self, i: usize) -> String {
+    match self.names().get(i.wrapping_sub(1)) {
+      Some(Some(name)) => format!("port `{name}`"),
+      _ if i == 0 => "the principal port".into(),
+      _ => format!("port {i}"),
+    }
+  }
+
+  pub fn show(&self, name: &str) -> String {
+    let mut out = format!("{name}(*");
+    for part in &self.auxiliary {
+      out += ", ";
+      out += &part.show(&|x| x.as_deref().unwrap_or("*").to_owned());
+    }
+    out += ")";
+    if !self.axioms.is_empty() {
+      let names = self.names();
+      let port = |i: usize| match names.get(i.wrapping_sub(1)) {
+        Some(Some(name)) => name.to_string(),
+        _ => i.to_string(),
+      };
+      let axioms = self
+        .axioms
+        .iter()
+        .map(|axiom| format!("{} {:?} {}", port(axiom.a), axiom.arrow, port(axiom.b)))
+        .collect::<Vec<_>>();
+      out += &format!(" {{ {} }}", axioms.join(", "));
+    }
+    out
+  }
 }
 
 #[derive(Debug, Clone)]
@@ -22,20 +146,149 @@ pub struct RuleDef {
   pub a: Node,
   pub b: Node,
   pub result: Vec<Node>,
+  pub constraints: Vec<Constraint>,
+  /// The verdict the source declares it expects, if any.
+  pub expect: Option<Verdict>,
+  /// The lints silenced for the rule.
+  pub allow: Vec<Lint>,
 }
 
 #[derive(Debug, Clone)]
 pub struct NetDef {
   pub name: String,
   pub vars: Scope<Var, ()>,
-  pub ports: Vec<Vec<Var>>,
+  pub ports: Vec<Partition<Var>>,
   pub nodes: Vec<Node>,
+  pub constraints: Vec<Constraint>,
+  /// The verdict the source declares it expects, if any.
+  pub expect: Option<Verdict>,
+  /// The lints silenced for the net.
+  pub allow: Vec<Lint>,
+}
+
+/// A relation between two variables of a rule or net, written like
+/// `assume a < b` or `assert x *|> y`.
+#[derive(Debug, Clone, Copy)]
+pub struct Constraint {
+  pub kind: ConstraintKind,
+  pub a: Var,
+  pub arrow: Arrow,
+  pub b: Var,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConstraintKind {
+  /// Narrows the relation before the diagram is completed.
+  Assume,
+  /// Is checked against the completed diagram.
+  Assert,
 }
 
 #[derive(Debug, Clone)]
 pub struct Node {
   pub agent: Agent,
   pub ports: Vec<Var>,
+  /// Where the name of the node's agent was written, for nodes read from a
+  /// source.
+  pub span: Option<Span>,
+}
+
+impl Node {
+  /// The node's auxiliary ports; none if it was given no ports at all, which
+  /// is reported as an arity error rather than here.
+  pub fn auxiliary(&self) -> &[Var] {
+    self.ports.get(1..).unwrap_or_default()
+  }
+}
+
+/// A range of bytes of a source, along with the line it starts on, counting
+/// from `1`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Span {
+  pub start: usize,
+  pub end: usize,
+  pub line: usize,
+}
+
+/// Whether an item is simple, as the checker decides or as the source expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Verdict {
+  Simple,
+  NonSimple,
+}
+
+impl Verdict {
+  /// The verdict named by `keyword`, as written in an expectation.
+  pub fn from_keyword(keyword: &str) -> Option<Self> {
+    match keyword {
+      "simple" => Some(Verdict::Simple),
+      "non_simple" => Some(Verdict::NonSimple),
+      _ => None,
+    }
+  }
+
+  pub fn keyword(self) -> &'static str {
+    match self {
+      Verdict::Simple => "simple",
+      Verdict::NonSimple => "non_simple",
+    }
+  }
+}
+
+impl Display for Verdict {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      Verdict::Simple => "simple",
+      Verdict::NonSimple => "non-simple",
+    })
+  }
+}
+
+/// A kind of suspicious definition, which an item silences with
+/// `#[allow(name)]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Lint {
+  /// An agent with no node in any rule or net.
+  UnusedAgent,
+  /// An agent in the active pair of no rule, though it is used.
+  AgentWithoutRules,
+  /// A rule whose agents are connected by their principal ports in no net or
+  /// result of a rule.
+  RuleNeverFires,
+  /// A net whose nodes fall into more than one connected part.
+  DisconnectedNet,
+  /// A variable of a rule or net connected to only one port.
+  SingleUseVar,
+}
+
+impl Lint {
+  pub const ALL: [Lint; 5] = [
+    Lint::UnusedAgent,
+    Lint::AgentWithoutRules,
+    Lint::RuleNeverFires,
+    Lint::DisconnectedNet,
+    Lint::SingleUseVar,
+  ];
+
+  pub fn name(self) -> &'static str {
+    match self {
+      Lint::UnusedAgent => "unused_agent",
+      Lint::AgentWithoutRules => "agent_without_rules",
+      Lint::RuleNeverFires => "rule_never_fires",
+      Lint::DisconnectedNet => "disconnected_net",
+      Lint::SingleUseVar => "single_use_var",
+    }
+  }
+
+  pub fn from_name(name: &str) -> Option<Self> {
+    Lint::ALL.into_iter().find(|lint| lint.name() == name)
+  }
+}
+
+impl Display for Lint {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(self.name())
+  }
 }
 
 new_idx!(pub Agent);
@@ -52,3 +305,101 @@ impl Debug for Var {
     write!(f, "v{}", self.0)
   }
 }
+
+impl Display for ConstraintKind {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    f.write_str(match self {
+      ConstraintKind::Assume => "assume",
+      ConstraintKind::Assert => "assert",
+    })
+  }
+}
+
+impl Display for System {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    for agent in self.agents.defs.values() {
+      show_attributes(f, None, &agent.value.allow)?;
+      writeln!(f, "agent {}", agent.value.show(&agent.name))?;
+    }
+    for rule in &self.rules {
+      let a = self.show_node(&rule.vars, &rule.a);
+      let b = self.show_node(&rule.vars, &rule.b);
+      writeln!(f)?;
+      show_attributes(f, rule.expect, &rule.allow)?;
+      writeln!(f, "rule {a} {b} {{")?;
+      for node in &rule.result {
+        writeln!(f, "  {}", self.show_node(&rule.vars, node))?;
+      }
+      show_constraints(f, &rule.vars, &rule.constraints)?;
+      writeln!(f, "}}")?;
+    }
+    for net in &self.nets {
+      let ports = net.ports.iter().map(|part| part.show(&|&v| net.vars.defs[v].name.clone()));
+      writeln!(f)?;
+      show_attributes(f, net.expect, &net.allow)?;
+      writeln!(f, "net {}({}) {{", net.name, ports.collect::<Vec<_>>().join(", "))?;
+      for node in &net.nodes {
+        writeln!(f, "  {}", self.show_node(&net.vars, node))?;
+      }
+      show_constraints(f, &net.vars, &net.constraints)?;
+      writeln!(f, "}}")?;
+    }
+    Ok(())
+  }
+}
+
+fn show_constraints(
+  f: &mut fmt::Formatter<'_>,
+  vars: &Scope<Var, ()>,
+  constraints: &[Constraint],
+) -> fmt::Result {
+  for constraint in constraints {
+    let a = &vars.defs[constraint.a].name;
+    let b = &vars.defs[constraint.b].name;
+    writeln!(f, "  {} {a} {:?} {b}", constraint.kind, constraint.arrow)?;
+  }
+  Ok(())
+}
+
+fn show_attributes(
+  f: &mut fmt::Formatter<'_>,
+  expect: Option<Verdict>,
+  allow: &[Lint],
+) -> fmt::Result {
+  if let Some(verdict) = expect {
+    writeln!(f, "#[expect({})]", verdict.keyword())?;
+  }
+  if !allow.is_empty() {
+    let lints = allow.iter().map(|lint| lint.name()).collect::<Vec<_>>();
+    writeln!(f, "#[allow({})]", lints.join(", "))?;
+  }
+  Ok(())
+}
+
+impl System {
+  fn show_node(&self, vars: &Scope<Var, ()>, node: &Node) -> String {
+    let ports = node.ports.iter().map(|&v| &*vars.defs[v].name).collect::<Vec<_>>();
+    format!("{}({})", self.agents.defs[node.agent].name, ports.join(", "))
+  }
+}
+
+#[test]
+fn shows_attributes() {
+  let src = "\
+#[allow(unused_agent, agent_without_rules)]
+agent A(*)
+
+#[expect(non_simple)]
+#[allow(rule_never_fires, single_use_var)]
+rule A(_) A(_) {
+}
+
+#[expect(simple)]
+#[allow(disconnected_net)]
+net n(x) {
+  A(x)
+}
+";
+  let system = crate::parser::SimplicityParser::parse(src).unwrap();
+  assert_eq!(system.to_string(), src);
+}
@@ -0,0 +1,228 @@
+use std::fmt::Write;
+
+use util::idx::IndexVec;
+
+use crate::{
+  arrow::Arrow,
+  ast::{System, Verdict},
+  check::Item,
+  diagram::{Diagram, NodeId, NodeType},
+};
+
+/// The closed diagram of an item, as recorded in a certificate file.
+#[derive(Debug, Clone)]
+pub struct Certificate {
+  /// The position of the item among those of its system, as the names of
+  /// rules for the same pair of agents are the same.
+  pub index: usize,
+  pub item: String,
+  pub nodes: Vec<NodeType>,
+  pub edges: Vec<(NodeId, NodeId, Arrow)>,
+}
+
+/// Writes a certificate for every simple item of `system`, returning the
+/// certificate file and the names of the items that could not be certified.
+pub fn certify(system: &System) -> (String, Vec<String>) {
+  let mut out = String::new();
+  let mut skipped = Vec::new();
+  for (index, item) in Item::all(system).enumerate() {
+    let mut diagram = item.diagram(system);
+    if Verdict::of(&mut diagram) == Verdict::NonSimple {
+      skipped.push(item.name(system));
+      continue;
+    }
+    writeln!(out, "certificate {index} {}", item.name(system)).unwrap();
+    for node in diagram.nodes.values() {
+      writeln!(out, "node {}", node_type_name(*node)).unwrap();
+    }
+    for (a, node) in diagram.graph.nodes.iter() {
+      let mut edges = node.edges.iter().filter(|(&b, _)| a < b).collect::<Vec<_>>();
+      edges.sort_by_key(|(&b, _)| b);
+      for (b, arrow) in edges {
+        writeln!(out, "edge {} {} {arrow:?}", a.0, b.0).unwrap();
+      }
+    }
+    writeln!(out).unwrap();
+  }
+  (out, skipped)
+}
+
+pub fn parse(src: &str) -> Result<Vec<Certificate>, String> {
+  let mut certificates = Vec::<Certificate>::new();
+  for (i, line) in src.lines().enumerate() {
+    let error = || format!("line {}: cannot parse `{line}`", i + 1);
+    let words = line.split_whitespace().collect::<Vec<_>>();
+    if let Some(header) = line.strip_prefix("certificate ") {
+      let (index, item) = header.trim().split_once(' ').ok_or_else(error)?;
+      certificates.push(Certificate {
+        index: index.parse().map_err(|_| error())?,
+        item: item.trim().into(),
+        nodes: Vec::new(),
+        edges: Vec::new(),
+      });
+      continue;
+    }
+    let certificate = match certificates.last_mut() {
+      Some(certificate) => certificate,
+      None if words.is_empty() => continue,
+      None => return Err(error()),
+    };
+    match words[..] {
+      [] => {}
+      ["node", name] => certificate.nodes.push(parse_node_type(name).ok_or_else(error)?),
+      ["edge", a, b, arrow] => {
+        let a = a.parse::<usize>().map_err(|_| error())?;
+        let b = b.parse::<usize>().map_err(|_| error())?;
+        let arrow = Arrow::parse(arrow).ok_or_else(error)?;
+        certificate.edges.push((NodeId(a), NodeId(b), arrow));
+      }
+      _ => return Err(error()),
+    }
+  }
+  Ok(certificates)
+}
+
+/// The certificate for the `index`th item of a system, which is called `name`.
+pub fn find<'a>(
+  certificates: &'a [Certificate],
+  index: usize,
+  name: &str,
+) -> Option<&'a Certificate> {
+  certificates.iter().find(|x| x.index == index && x.item == name)
+}
+
+/// Checks that `certificate` proves `item` simple: it must have the nodes of
+/// the item's diagram and entail every one of its edges, be closed under
+/// composition, and contain no contradictory edge. None of this relies on
+/// computing the closure.
+pub fn verify(system: &System, item: Item, certificate: &Certificate) -> Result<(), String> {
+  let source = item.diagram(system);
+  if certificate.nodes[..] != source.nodes.values().copied().collect::<Vec<_>>()[..] {
+    return Err("the nodes do not match the source".into());
+  }
+
+  let mut diagram =
+    Diagram { nodes: IndexVec::from(certificate.nodes.clone()), ..Default::default() };
+  for &(a, b, arrow) in &certificate.edges {
+    if a.0 >= certificate.nodes.len() || b.0 >= certificate.nodes.len() || a == b {
+      return Err(format!("edge {} {} is not between two distinct nodes", a.0, b.0));
+    }
+    diagram.graph.insert(a, b, arrow);
+  }
+
+  for (a, node) in source.graph.nodes.iter() {
+    for (&b, &arrow) in &node.edges {
+      match diagram.graph.get_edge(a, b) {
+        Some(x) if x.0 & !arrow.0 == 0 => {}
+        _ => return Err(format!("edge {} {} does not entail {arrow:?}", a.0, b.0)),
+      }
+    }
+  }
+  if diagram.is_contradictory() {
+    return Err("an edge is contradictory".into());
+  }
+  if !diagram.is_complete() {
+    return Err("the diagram is not closed".into());
+  }
+  Ok(())
+}
+
+fn node_type_name(node: NodeType) -> &'static str {
+  match node {
+    NodeType::Principal => "principal",
+    NodeType::Auxiliary => "auxiliary",
+    NodeType::Partition => "partition",
+  }
+}
+
+fn parse_node_type(name: &str) -> Option<NodeType> {
+  match name {
+    "principal" => Some(NodeType::Principal),
+    "auxiliary" => Some(NodeType::Auxiliary),
+    "partition" => Some(NodeType::Partition),
+    _ => None,
+  }
+}
+
+#[cfg(test)]
+const SYSTEM: &str = "
+agent Con(*, *, *)
+agent Era(*)
+
+rule Con(_, a, b) Era(_) {
+  Era(a)
+  Era(b)
+}
+
+rule Con(_, a, b) Con(_, c, d) {
+  Con(a, x, y)
+  Con(b, z, w)
+  Con(c, x, z)
+  Con(d, y, w)
+}
+";
+
+#[test]
+fn certifies_simple_items() {
+  let system = crate::parser::SimplicityParser::parse(SYSTEM).unwrap();
+  let (out, skipped) = certify(&system);
+  assert_eq!(skipped, ["rule Con/Con"]);
+  let certificates = parse(&out).unwrap();
+  let [certificate] = &certificates[..] else { panic!() };
+  assert_eq!((certificate.index, &*certificate.item), (0, "rule Con/Era"));
+  assert!(!certificate.edges.is_empty());
+  assert_eq!(verify(&system, Item::Rule(0), certificate), Ok(()));
+}
+
+#[test]
+fn rejects_tampered_certificates() {
+  let system = crate::parser::SimplicityParser::parse(SYSTEM).unwrap();
+  let certificate = &parse(&certify(&system).0).unwrap()[0];
+  let verify = |f: &dyn Fn(&mut Certificate)| {
+    let mut certificate = certificate.clone();
+    f(&mut certificate);
+    verify(&system, Item::Rule(0), &certificate).unwrap_err()
+  };
+  assert_eq!(verify(&|x| x.nodes.push(NodeType::Auxiliary)), "the nodes do not match the source");
+  assert_eq!(
+    verify(&|x| x.edges.push((NodeId(0), NodeId(99), Arrow(0b00100)))),
+    "edge 0 99 is not between two distinct nodes"
+  );
+  let (a, b, _) = certificate.edges[0];
+  assert_eq!(verify(&|x| x.edges.push((a, b, Arrow(0)))), "an edge is contradictory");
+  let loosen = |x: &mut Certificate| x.edges.iter_mut().for_each(|edge| edge.2 = Arrow(0b11111));
+  assert!(verify(&loosen).contains("does not entail"));
+  assert!(verify(&|x| x.edges.clear()).contains("does not entail"));
+}
+
+#[test]
+fn rejects_malformed_certificates() {
+  assert_eq!(parse("node principal").unwrap_err(), "line 1: cannot parse `node principal`");
+  assert_eq!(
+    parse("certificate 0 net n\nnode principle").unwrap_err(),
+    "line 2: cannot parse `node principle`"
+  );
+  assert!(parse("certificate 0 net n\nedge 0 x --*--").is_err());
+  assert!(parse("certificate 0 net n\nedge 0 1 ???").is_err());
+  assert!(parse("certificate 0 net n\nedge 0 1").is_err());
+  assert!(parse("certificate net n").is_err());
+  let certificates = parse("\ncertificate 0 net n\nnode principal\n\nnode auxiliary\n").unwrap();
+  assert_eq!(certificates[0].nodes, [NodeType::Principal, NodeType::Auxiliary]);
+}
+
+#[test]
+fn tells_apart_items_with_the_same_name() {
+  let src = "
+    agent Con(*, *, *)
+    agent Era(*)
+    rule Con(_, a, b) Era(_) { Era(a) Era(b) }
+    rule Con(_, a, b) Era(_) { Con(a, b, x) Era(x) }
+  ";
+  let system = crate::parser::SimplicityParser::parse(src).unwrap();
+  let certificates = parse(&certify(&system).0).unwrap();
+  let second = find(&certificates, 1, "rule Con/Era").unwrap();
+  assert_eq!(second.index, 1);
+  assert_eq!(verify(&system, Item::Rule(1), second), Ok(()));
+  assert!(verify(&system, Item::Rule(1), &certificates[0]).is_err());
+  assert!(find(&certificates, 1, "rule Era/Con").is_none());
+}
@@ -0,0 +1,281 @@
+use util::graph::Edge;
+
+use crate::{
+  ast::{
+    Agent, Constraint, ConstraintKind, Lint, NetDef, Node, Partition, RuleDef, Span, System, Var,
+    Verdict,
+  },
+  diagram::{Diagram, NodeId, Origin},
+  scope::Scope,
+};
+
+impl Verdict {
+  pub fn of(diagram: &mut Diagram) -> Self {
+    diagram.complete();
+    assert!(diagram.is_complete());
+    if diagram.is_contradictory() {
+      Verdict::NonSimple
+    } else {
+      Verdict::Simple
+    }
+  }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Item {
+  Rule(usize),
+  Net(usize),
+}
+
+impl Item {
+  pub fn all(system: &System) -> impl Iterator<Item = Item> {
+    (0..system.rules.len()).map(Item::Rule).chain((0..system.nets.len()).map(Item::Net))
+  }
+
+  pub fn name(self, system: &System) -> String {
+    match self {
+      Item::Rule(i) => format!("rule {}", rule_name(system, &system.rules[i])),
+      Item::Net(i) => format!("net {}", system.nets[i].name),
+    }
+  }
+
+  /// Every node of the item, including the active pair of a rule.
+  pub fn nodes(self, system: &System) -> Vec<&Node> {
+    match self {
+      Item::Rule(i) => {
+        let rule = &system.rules[i];
+        [&rule.a, &rule.b].into_iter().chain(&rule.result).collect()
+      }
+      Item::Net(i) => system.nets[i].nodes.iter().collect(),
+    }
+  }
+
+  pub fn agents(self, system: &System) -> Vec<Agent> {
+    self.nodes(system).into_iter().map(|x| x.agent).collect()
+  }
+
+  /// Describes every node of the item that is given the wrong number of ports
+  /// for its agent.
+  pub fn arity_errors(self, system: &System) -> Vec<String> {
+    self.nodes(system).into_iter().filter_map(|node| arity_error(system, node)).collect()
+  }
+
+  /// The verdict the item declares it expects, if any.
+  pub fn expect(self, system: &System) -> Option<Verdict> {
+    match self {
+      Item::Rule(i) => system.rules[i].expect,
+      Item::Net(i) => system.nets[i].expect,
+    }
+  }
+
+  /// The lints the item silences.
+  pub fn allow(self, system: &System) -> &[Lint] {
+    match self {
+      Item::Rule(i) => &system.rules[i].allow,
+      Item::Net(i) => &system.nets[i].allow,
+    }
+  }
+
+  pub fn vars(self, system: &System) -> &Scope<Var, ()> {
+    match self {
+      Item::Rule(i) => &system.rules[i].vars,
+      Item::Net(i) => &system.nets[i].vars,
+    }
+  }
+
+  /// The variables of the item's free ports, in the order they are inserted
+  /// into its diagram.
+  pub fn ports(self, system: &System) -> Vec<Var> {
+    match self {
+      Item::Rule(i) => {
+        let rule = &system.rules[i];
+        rule.a.auxiliary().iter().chain(rule.b.auxiliary()).copied().collect()
+      }
+      Item::Net(i) => system.nets[i].ports.iter().flat_map(Partition::ports).copied().collect(),
+    }
+  }
+
+  pub fn diagram(self, system: &System) -> Diagram {
+    match self {
+      Item::Rule(i) => rule_diagram(system, &system.rules[i]),
+      Item::Net(i) => net_diagram(system, &system.nets[i]),
+    }
+  }
+
+  pub fn check(self, system: &System) -> Verdict {
+    Verdict::of(&mut self.diagram(system))
+  }
+
+  pub fn constraints(self, system: &System) -> &[Constraint] {
+    match self {
+      Item::Rule(i) => &system.rules[i].constraints,
+      Item::Net(i) => &system.nets[i].constraints,
+    }
+  }
+
+  /// Describes where a node of the item's `diagram` came from, such as
+  /// ``port 2 of `Disj` on line 7``.
+  pub fn describe_node(self, system: &System, diagram: &Diagram, node: NodeId) -> String {
+    let agent = |agent: Agent, span: Option<Span>| {
+      let name = &system.agents.defs[agent].name;
+      match span {
+        Some(span) => format!("`{name}` on line {}", span.line),
+        None => format!("`{name}`"),
+      }
+    };
+    match diagram.origins.get(node) {
+      None => format!("node {node:?}"),
+      Some(&Origin::Port { agent: a, span, port }) => {
+        format!("{} of {}", system.agents.defs[a].value.describe_port(port), agent(a, span))
+      }
+      Some(&Origin::Partition { agent: a, span }) => {
+        format!("a group of the ports of {}", agent(a, span))
+      }
+      Some(&Origin::FreePort(i)) => match self.ports(system).get(i) {
+        Some(&var) => format!("free port `{}`", self.vars(system).defs[var].name),
+        None => format!("free port {i}"),
+      },
+      Some(Origin::FreePartition) => "a group of the free ports".into(),
+      Some(&Origin::Link(a, b)) => format!(
+        "the link between {} and {}",
+        self.describe_node(system, diagram, a),
+        self.describe_node(system, diagram, b)
+      ),
+    }
+  }
+
+  /// Describes every assertion of the item that does not hold in its
+  /// completed `diagram`.
+  pub fn failed_assertions(self, system: &System, diagram: &Diagram) -> Vec<String> {
+    let vars = self.vars(system);
+    let mut failures = Vec::new();
+    for constraint in self.constraints(system) {
+      let derived = diagram.relation(constraint.a, constraint.b);
+      if constraint.kind == ConstraintKind::Assert && derived.merge(constraint.arrow) != derived {
+        failures.push(format!(
+          "assertion `{} {:?} {}` does not hold; the derived relation is `{derived:?}`",
+          vars.defs[constraint.a].name, constraint.arrow, vars.defs[constraint.b].name,
+        ));
+      }
+    }
+    failures
+  }
+}
+
+pub fn rule_name(system: &System, rule: &RuleDef) -> String {
+  let a = &system.agents.defs[rule.a.agent];
+  let b = &system.agents.defs[rule.b.agent];
+  format!("{}/{}", a.name, b.name)
+}
+
+pub fn rule_diagram(system: &System, rule: &RuleDef) -> Diagram {
+  let mut diagram = Diagram::default();
+
+  let a = &system.agents.defs[rule.a.agent].value;
+  let b = &system.agents.defs[rule.b.agent].value;
+  diagram.insert_auxiliary_ports(&rule.a, a);
+  diagram.insert_auxiliary_ports(&rule.b, b);
+
+  for node in &rule.result {
+    let agent = &system.agents.defs[node.agent].value;
+    diagram.insert_agent(node, agent);
+  }
+  assume(&mut diagram, &rule.constraints);
+
+  diagram
+}
+
+pub fn net_diagram(system: &System, net: &NetDef) -> Diagram {
+  let mut diagram = Diagram::default();
+
+  diagram.insert_free_ports(net.ports.iter().flat_map(Partition::ports).copied(), &net.ports);
+
+  for node in &net.nodes {
+    let agent = &system.agents.defs[node.agent].value;
+    diagram.insert_agent(node, agent);
+  }
+  assume(&mut diagram, &net.constraints);
+
+  diagram
+}
+
+/// Describes how `node` is given the wrong number of ports for its agent, if
+/// it is.
+pub fn arity_error(system: &System, node: &Node) -> Option<String> {
+  let agent = &system.agents.defs[node.agent];
+  let (arity, ports) = (agent.value.arity(), node.ports.len());
+  (ports != arity).then(|| {
+    format!("agent `{}` has {arity} ports, but {ports} were given", agent.value.show(&agent.name))
+  })
+}
+
+/// Narrows `diagram` by the assumptions among `constraints`.
+pub fn assume(diagram: &mut Diagram, constraints: &[Constraint]) {
+  for constraint in constraints.iter().filter(|x| x.kind == ConstraintKind::Assume) {
+    diagram.assume(constraint.a, constraint.b, constraint.arrow);
+  }
+}
+
+#[cfg(test)]
+fn erase_both(constraint: &str) -> System {
+  let src = format!(
+    "agent Con(*, {{*, *}}) agent Era(*)
+    net n(r) {{ Con(r, a, b) Era(a) Era(b) {constraint} }}"
+  );
+  crate::parser::SimplicityParser::parse(&src).unwrap()
+}
+
+#[test]
+fn assumptions_narrow_the_diagram() {
+  assert_eq!(Item::Net(0).check(&erase_both("")), Verdict::Simple);
+  assert_eq!(Item::Net(0).check(&erase_both("assume r --*-- a")), Verdict::Simple);
+  assert_eq!(Item::Net(0).check(&erase_both("assume r <<--- a")), Verdict::NonSimple);
+}
+
+#[test]
+fn reports_failed_assertions() {
+  let failures = |constraint| {
+    let system = erase_both(constraint);
+    let mut diagram = Item::Net(0).diagram(&system);
+    Verdict::of(&mut diagram);
+    Item::Net(0).failed_assertions(&system, &diagram)
+  };
+  assert_eq!(failures("assert r --*-- a assert r <<*>> b"), [] as [String; 0]);
+  assert_eq!(
+    failures("assert r --*-- a assert a <<--- r"),
+    ["assertion `a <<--- r` does not hold; the derived relation is `--*--`"]
+  );
+}
+
+#[test]
+fn describes_nodes() {
+  let src = "agent Cons(*, head, tail)
+agent Nil(*)
+rule Cons(_, h, t) Nil(_) {
+  Nil(h)
+  Nil(t)
+}
+net n(x) { Nil(x) }
+";
+  let system = crate::parser::SimplicityParser::parse(src).unwrap();
+  let describe = |item: Item, nodes: &[usize]| {
+    let diagram = item.diagram(&system);
+    nodes.iter().map(|&x| item.describe_node(&system, &diagram, NodeId(x))).collect::<Vec<_>>()
+  };
+  assert_eq!(
+    describe(Item::Rule(0), &[0, 1, 3, 4, 5, 8, 99]),
+    [
+      "a group of the ports of `Cons` on line 3",
+      "port `head` of `Cons` on line 3",
+      "port `tail` of `Cons` on line 3",
+      "the principal port of `Nil` on line 4",
+      "the link between the principal port of `Nil` on line 4 and port `head` of `Cons` on line 3",
+      "the principal port of `Nil` on line 5",
+      "node 99",
+    ]
+  );
+  assert_eq!(
+    describe(Item::Net(0), &[0, 1, 2]),
+    ["a group of the free ports", "free port `x`", "the principal port of `Nil` on line 7"]
+  );
+}
@@ -9,13 +9,23 @@ use util::{
   new_idx,
 };
 
-use crate::{arrow::Arrow, ast::Var};
+use crate::{
+  arrow::Arrow,
+  ast::{Agent, AgentDef, Node, Partition, Span, Var},
+};
 
-#[derive(Debug, Default)]
+#[derive(Debug, Default, Clone)]
 pub struct Diagram {
   pub vars: IntMap<Var, NodeId>,
   pub nodes: IndexVec<NodeId, NodeType>,
+  /// Where each node came from. Diagrams built by hand, rather than by the
+  /// methods below, may leave this empty.
+  pub origins: IndexVec<NodeId, Origin>,
   pub graph: Graph<NodeId, Arrow>,
+  pub free_ports: Vec<NodeId>,
+  /// The port each variable was first attached to, which stands for the
+  /// variable when asking about its relation to others.
+  pub endpoints: IntMap<Var, NodeId>,
 }
 
 new_idx!(pub NodeId);
@@ -27,47 +37,139 @@ pub enum NodeType {
   Partition,
 }
 
+/// What a node of a diagram stands for.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Origin {
+  /// The `port`th port of a node of `agent`, where `0` is the principal port.
+  Port { agent: Agent, span: Option<Span>, port: usize },
+  /// A group of the auxiliary ports of a node of `agent`.
+  Partition { agent: Agent, span: Option<Span> },
+  /// The `i`th free port.
+  FreePort(usize),
+  /// A group of the free ports.
+  FreePartition,
+  /// One of the nodes that join two ports.
+  Link(NodeId, NodeId),
+}
+
 impl Diagram {
-  pub fn insert_free_ports(
+  pub fn insert_free_ports<T>(
     &mut self,
     vars: impl IntoIterator<Item = Var>,
-    partition: impl IntoIterator<Item = usize>,
+    partition: &[Partition<T>],
   ) {
-    let mut vars = vars.into_iter();
-    for n in partition {
-      let x = self.nodes.push(NodeType::Partition);
-      for _ in 0..n {
-        let v = vars.next().unwrap();
-        let p = self.nodes.push(NodeType::Principal);
-        self.graph.insert(x, p, Arrow(0b00001));
-        self.link_var(v, p);
-      }
+    let ports = self.add_free_ports(partition);
+    self.link_vars(vars, ports);
+  }
+
+  pub fn insert_agent(&mut self, node: &Node, def: &AgentDef) {
+    let ports = self.add_agent(node, def);
+    self.link_vars(node.ports.iter().copied(), ports);
+  }
+
+  /// Inserts the auxiliary ports of `node` as free ports, grouped by the
+  /// partition of its agent and related by those of its axioms that do not
+  /// involve its principal port.
+  pub fn insert_auxiliary_ports(&mut self, node: &Node, def: &AgentDef) {
+    let (agent, span) = (node.agent, node.span);
+    let start = self.nodes.next_index();
+    let ports = self.add_free_ports(&def.auxiliary);
+    for (_, origin) in self.origins.iter_mut().filter(|&(x, _)| x >= start) {
+      *origin = Origin::Partition { agent, span };
     }
-    assert!(vars.next().is_none());
+    for (i, &port) in ports.iter().enumerate() {
+      self.origins[port] = Origin::Port { agent, span, port: i + 1 };
+    }
+    for axiom in def.axioms.iter().filter(|x| x.a != 0 && x.b != 0) {
+      self.graph.insert(ports[axiom.a - 1], ports[axiom.b - 1], axiom.arrow);
+    }
+    self.link_vars(node.auxiliary().iter().copied(), ports);
+  }
+
+  /// Adds free ports grouped by `partition` without connecting them to
+  /// anything, returning their nodes.
+  pub fn add_free_ports<T>(&mut self, partition: &[Partition<T>]) -> Vec<NodeId> {
+    let mut ports = Vec::new();
+    for part in partition {
+      let x = self.add_node(NodeType::Partition, Origin::FreePartition);
+      self.add_group(x, part, NodeType::Principal, Arrow(0b00001), &mut ports);
+    }
+    let first = self.free_ports.len();
+    for (i, &port) in ports.iter().enumerate() {
+      self.origins[port] = Origin::FreePort(first + i);
+    }
+    self.free_ports.extend(&ports);
+    ports
   }
 
-  pub fn insert_agent(
+  /// Adds a node of an agent without connecting its ports to anything,
+  /// returning the nodes of its principal and auxiliary ports.
+  pub fn add_agent(&mut self, node: &Node, def: &AgentDef) -> Vec<NodeId> {
+    let (agent, span) = (node.agent, node.span);
+    let principal = self.add_node(NodeType::Principal, Origin::Port { agent, span, port: 0 });
+    let mut ports = vec![principal];
+    for part in &def.auxiliary {
+      let x = self.add_node(NodeType::Partition, Origin::Partition { agent, span });
+      self.graph.insert(principal, x, Arrow(0b00001));
+      self.add_group(x, part, NodeType::Auxiliary, Arrow(0b00011), &mut ports);
+    }
+    for (i, &port) in ports.iter().enumerate() {
+      self.origins[port] = Origin::Port { agent, span, port: i };
+    }
+    for axiom in &def.axioms {
+      self.graph.insert(ports[axiom.a], ports[axiom.b], axiom.arrow);
+    }
+    ports
+  }
+
+  /// Adds the ports of `part` below the partition node `x`, each as a node of
+  /// type `port` joined to it by `arrow`. Each group nested in `part` gets a
+  /// partition node of its own, which stands to `x` as the partitions of an
+  /// agent do to its principal port. The new nodes take the origin of `x`,
+  /// and the caller fills in those of the ports.
+  fn add_group<T>(
     &mut self,
-    vars: impl IntoIterator<Item = Var>,
-    partition: impl IntoIterator<Item = usize>,
+    x: NodeId,
+    part: &Partition<T>,
+    port: NodeType,
+    arrow: Arrow,
+    ports: &mut Vec<NodeId>,
   ) {
-    let principal = self.nodes.push(NodeType::Principal);
-    let mut vars = vars.into_iter();
-    self.link_var(vars.next().unwrap(), principal);
-    for n in partition {
-      let partition = self.nodes.push(NodeType::Partition);
-      self.graph.insert(principal, partition, Arrow(0b00001));
-      for _ in 0..n {
-        let v = vars.next().unwrap();
-        let aux = self.nodes.push(NodeType::Auxiliary);
-        self.graph.insert(partition, aux, Arrow(0b00011));
-        self.link_var(v, aux);
+    let parts = match part {
+      Partition::Port(_) => std::slice::from_ref(part),
+      Partition::Group(parts) => parts,
+    };
+    for part in parts {
+      match part {
+        Partition::Port(_) => {
+          let p = self.add_node(port, self.origins[x]);
+          self.graph.insert(x, p, arrow);
+          ports.push(p);
+        }
+        Partition::Group(_) => {
+          let y = self.add_node(NodeType::Partition, self.origins[x]);
+          self.graph.insert(x, y, Arrow(0b00001));
+          self.add_group(y, part, port, arrow, ports);
+        }
       }
     }
+  }
+
+  fn add_node(&mut self, ty: NodeType, origin: Origin) -> NodeId {
+    self.origins.push(origin);
+    self.nodes.push(ty)
+  }
+
+  fn link_vars(&mut self, vars: impl IntoIterator<Item = Var>, ports: Vec<NodeId>) {
+    let mut vars = vars.into_iter();
+    for port in ports {
+      self.link_var(vars.next().unwrap(), port);
+    }
     assert!(vars.next().is_none());
   }
 
   fn link_var(&mut self, var: Var, node: NodeId) {
+    self.endpoints.entry(var).or_insert(node);
     match self.vars.entry(var) {
       Entry::Occupied(e) => {
         let other = e.remove();
@@ -79,14 +181,37 @@ impl Diagram {
     }
   }
 
+  /// Narrows the relation between the endpoints of `a` and `b`, before the
+  /// diagram is completed. Both must be attached to a port of the diagram, as
+  /// the parser ensures for the variables of constraints, and distinct, as it
+  /// also ensures.
+  pub fn assume(&mut self, a: Var, b: Var, arrow: Arrow) {
+    debug_assert_ne!(a, b);
+    self.graph.insert(self.endpoints[&a], self.endpoints[&b], arrow);
+  }
+
+  /// The relation between the endpoints of `a` and `b`.
+  pub fn relation(&self, a: Var, b: Var) -> Arrow {
+    self.graph.get_edge(self.endpoints[&a], self.endpoints[&b]).unwrap_or(Arrow(0b11111))
+  }
+
   pub fn complete(&mut self) {
     self.graph.closure(|a, x, _, y, c| if a != c { Arrow::join(x, y) } else { None });
   }
 
+  /// Narrows the edge between `a` and `b` in a completed diagram, and
+  /// completes it again from there.
+  pub fn refine(&mut self, a: NodeId, b: NodeId, arrow: Arrow) {
+    self.graph.insert(a, b, arrow);
+    self
+      .graph
+      .closure_from([(a, b)], |a, x, _, y, c| if a != c { Arrow::join(x, y) } else { None });
+  }
+
   pub fn link(&mut self, a: NodeId, b: NodeId) {
-    let x = self.nodes.push(NodeType::Partition);
-    let y = self.nodes.push(NodeType::Partition);
-    let z = self.nodes.push(NodeType::Partition);
+    let x = self.add_node(NodeType::Partition, Origin::Link(a, b));
+    let y = self.add_node(NodeType::Partition, Origin::Link(a, b));
+    let z = self.add_node(NodeType::Partition, Origin::Link(a, b));
     self.graph.insert(x, y, Arrow(0b00001));
     self.graph.insert(x, z, Arrow(0b00001));
     self.graph.insert(y, a, Arrow(0b00011));
@@ -119,3 +244,87 @@ impl Debug for NodeId {
     write!(f, "{}", self.0)
   }
 }
+
+/// Every edge of `diagram` as `(a, b, arrow)` with `a < b`, in order.
+#[cfg(test)]
+fn edges(diagram: &Diagram) -> Vec<(usize, usize, u8)> {
+  let mut edges = Vec::new();
+  for (a, node) in diagram.graph.nodes.iter() {
+    edges.extend(node.edges.iter().filter(|(&b, _)| a < b).map(|(&b, &x)| (a.0, b.0, x.0)));
+  }
+  edges.sort();
+  edges
+}
+
+#[test]
+fn builds_nested_partitions() {
+  use crate::parser::SimplicityParser;
+  use NodeType::*;
+  let system = SimplicityParser::parse("agent F(*, {*, {*, *}}, *)").unwrap();
+  assert_eq!(system.to_string(), "agent F(*, {*, {*, *}}, *)\n");
+  let def = &system.agents.defs[Agent(0)].value;
+  let node = Node { agent: Agent(0), ports: Vec::new(), span: None };
+
+  let mut diagram = Diagram::default();
+  let ports = diagram.add_agent(&node, def);
+  assert_eq!(ports, [0, 2, 4, 5, 7].map(NodeId));
+  let types =
+    [Principal, Partition, Auxiliary, Partition, Auxiliary, Auxiliary, Partition, Auxiliary];
+  assert_eq!(diagram.nodes.values().copied().collect::<Vec<_>>(), types);
+  // Each group stands to the one around it as the groups of the agent do to
+  // its principal port.
+  let expected = [(0, 1, 1), (0, 6, 1), (1, 2, 3), (1, 3, 1), (3, 4, 3), (3, 5, 3), (6, 7, 3)];
+  assert_eq!(edges(&diagram), expected);
+
+  let mut diagram = Diagram::default();
+  let ports = diagram.add_free_ports(&def.auxiliary);
+  assert_eq!(ports, [1, 3, 4, 6].map(NodeId));
+  assert_eq!(edges(&diagram), [(0, 1, 1), (0, 2, 1), (2, 3, 1), (2, 4, 1), (5, 6, 1)]);
+}
+
+#[test]
+fn adds_axioms() {
+  use crate::parser::SimplicityParser;
+  let system = SimplicityParser::parse("agent A(*, x, y) { 0 --->- x, x --*-> y }").unwrap();
+  let def = &system.agents.defs[Agent(0)].value;
+  let node = Node { agent: Agent(0), ports: [0, 1, 2].map(Var).to_vec(), span: None };
+
+  let mut diagram = Diagram::default();
+  diagram.insert_agent(&node, def);
+  let expected = [(0, 1, 1), (0, 2, 2), (0, 3, 1), (1, 2, 3), (2, 4, 5), (3, 4, 3)];
+  assert_eq!(edges(&diagram), expected);
+  assert_eq!(diagram.relation(Var(1), Var(2)), Arrow(0b00101));
+
+  // Only the axioms between auxiliary ports carry over to free ports.
+  let mut diagram = Diagram::default();
+  diagram.insert_auxiliary_ports(&node, def);
+  assert_eq!(edges(&diagram), [(0, 1, 1), (1, 3, 5), (2, 3, 1)]);
+  assert_eq!(diagram.relation(Var(1), Var(2)), Arrow(0b00101));
+}
+
+#[test]
+fn records_origins() {
+  use crate::parser::SimplicityParser;
+  use Origin::*;
+  let system = SimplicityParser::parse("agent F(*, {*, *})").unwrap();
+  let def = &system.agents.defs[Agent(0)].value;
+  let span = Some(Span { start: 0, end: 1, line: 1 });
+  let node = Node { agent: Agent(0), ports: [0, 1, 2].map(Var).to_vec(), span };
+  let (agent, port) = (Agent(0), |port| Port { agent: Agent(0), span, port });
+
+  let mut diagram = Diagram::default();
+  diagram.add_agent(&node, def);
+  assert_eq!(
+    diagram.origins.values().copied().collect::<Vec<_>>(),
+    [port(0), Partition { agent, span }, port(1), port(2)]
+  );
+
+  // The nodes added before the auxiliary ports keep their origins.
+  let mut diagram = Diagram::default();
+  diagram.add_free_ports(&[crate::ast::Partition::Port(())]);
+  diagram.insert_auxiliary_ports(&node, def);
+  assert_eq!(
+    diagram.origins.values().copied().collect::<Vec<_>>(),
+    [FreePartition, FreePort(0), Partition { agent, span }, port(1), port(2)]
+  );
+}
@@ -0,0 +1,142 @@
+use std::fmt::{self, Display, Write};
+
+use crate::{
+  ast::{System, Verdict},
+  check::Item,
+};
+
+/// How the verdict of an item differs between two versions of a system.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Change {
+  BecameSimple,
+  BecameNonSimple,
+  Added(Verdict),
+  Removed(Verdict),
+}
+
+impl Display for Change {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Change::BecameSimple => write!(f, "became simple"),
+      Change::BecameNonSimple => write!(f, "became non-simple"),
+      Change::Added(verdict) => write!(f, "added ({verdict})"),
+      Change::Removed(verdict) => write!(f, "removed (was {verdict})"),
+    }
+  }
+}
+
+/// The name and verdict of every item of `system`. Rules are named by their
+/// pair of agents.
+pub fn verdicts(system: &System) -> Vec<(String, Verdict)> {
+  Item::all(system).map(|item| (item.name(system), item.check(system))).collect()
+}
+
+/// Writes verdicts as a baseline, one `name: verdict` line per item, as the
+/// checker prints them.
+pub fn write_baseline(verdicts: &[(String, Verdict)]) -> String {
+  let mut out = String::new();
+  for (name, verdict) in verdicts {
+    writeln!(out, "{name}: {verdict}").unwrap();
+  }
+  out
+}
+
+pub fn read_baseline(src: &str) -> Result<Vec<(String, Verdict)>, String> {
+  let mut verdicts = Vec::new();
+  for (i, line) in src.lines().enumerate().filter(|(_, x)| !x.trim().is_empty()) {
+    let verdict = line.rsplit_once(": ").and_then(|(name, verdict)| {
+      let verdict = match verdict.trim() {
+        "simple" => Verdict::Simple,
+        "non-simple" => Verdict::NonSimple,
+        _ => return None,
+      };
+      Some((name.trim().to_owned(), verdict))
+    });
+    verdicts.push(verdict.ok_or_else(|| format!("line {}: expected `name: verdict`", i + 1))?);
+  }
+  Ok(verdicts)
+}
+
+/// The items whose verdict differs between `old` and `new`, in the order of
+/// `new` followed by the removed items. Items that share a name, such as two
+/// rules for the same pair of agents, are matched in order.
+pub fn diff(old: &[(String, Verdict)], new: &[(String, Verdict)]) -> Vec<(String, Change)> {
+  let mut matched = vec![false; old.len()];
+  let mut changes = Vec::new();
+  for (name, verdict) in new {
+    let found = (0..old.len()).find(|&i| !matched[i] && old[i].0 == *name);
+    let change = match found {
+      None => Some(Change::Added(*verdict)),
+      Some(i) => {
+        matched[i] = true;
+        match (old[i].1, verdict) {
+          (Verdict::NonSimple, Verdict::Simple) => Some(Change::BecameSimple),
+          (Verdict::Simple, Verdict::NonSimple) => Some(Change::BecameNonSimple),
+          _ => None,
+        }
+      }
+    };
+    changes.extend(change.map(|change| (name.clone(), change)));
+  }
+  for (i, (name, verdict)) in old.iter().enumerate() {
+    if !matched[i] {
+      changes.push((name.clone(), Change::Removed(*verdict)));
+    }
+  }
+  changes
+}
+
+#[test]
+fn finds_changed_verdicts() {
+  let system = |fn_ports, nets| {
+    let src = format!(
+      "agent Fn(*, {fn_ports}) agent Add(*, *, *) agent Num(*)
+      net main(r) {{ Fn(r, a, b) Add(a, b, n) Num(n) }}
+      {nets}"
+    );
+    verdicts(&crate::parser::SimplicityParser::parse(&src).unwrap())
+  };
+  let old = system("*, *", "net gone(r) { Num(r) }");
+  let new = system("{*, *}", "net new(r) { Num(r) }");
+  assert_eq!(old, [("net main".into(), Verdict::NonSimple), ("net gone".into(), Verdict::Simple)]);
+  assert_eq!(
+    diff(&old, &new),
+    [
+      ("net main".into(), Change::BecameSimple),
+      ("net new".into(), Change::Added(Verdict::Simple)),
+      ("net gone".into(), Change::Removed(Verdict::Simple)),
+    ]
+  );
+  assert_eq!(diff(&new, &old)[0], ("net main".into(), Change::BecameNonSimple));
+  assert_eq!(diff(&old, &old), []);
+}
+
+#[test]
+fn matches_items_with_the_same_name_in_order() {
+  let item = |verdict| ("rule A/B".to_owned(), verdict);
+  let old = [item(Verdict::Simple), item(Verdict::NonSimple)];
+  let new = [item(Verdict::Simple), item(Verdict::Simple), item(Verdict::NonSimple)];
+  assert_eq!(
+    diff(&old, &new),
+    [
+      ("rule A/B".into(), Change::BecameSimple),
+      ("rule A/B".into(), Change::Added(Verdict::NonSimple)),
+    ]
+  );
+}
+
+#[test]
+fn reads_baselines() {
+  let verdicts = [
+    ("rule Con/Dup".to_owned(), Verdict::Simple),
+    ("net with: colon".to_owned(), Verdict::NonSimple),
+  ];
+  let baseline = write_baseline(&verdicts);
+  assert_eq!(baseline, "rule Con/Dup: simple\nnet with: colon: non-simple\n");
+  assert_eq!(read_baseline(&format!("\n{baseline}\n")).unwrap(), verdicts);
+  assert_eq!(
+    read_baseline("net a: simple\nnet b: maybe").unwrap_err(),
+    "line 2: expected `name: verdict`"
+  );
+  assert_eq!(read_baseline("net a simple").unwrap_err(), "line 1: expected `name: verdict`");
+}
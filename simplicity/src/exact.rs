@@ -0,0 +1,97 @@
+use std::fmt::{self, Display};
+
+use crate::{arrow::Arrow, diagram::Diagram};
+
+/// The most nodes a diagram may have to be searched for a scenario, since the
+/// search may take time exponential in the number of pairs of nodes.
+pub const MAX_NODES: usize = 64;
+
+/// A diagram with more than [`MAX_NODES`] nodes, which is not searched.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TooLarge {
+  pub nodes: usize,
+}
+
+impl Display for TooLarge {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "too large: {} nodes, but at most {MAX_NODES} are searched", self.nodes)
+  }
+}
+
+/// Decides whether `diagram` has a scenario: an assignment of a single atomic
+/// relation to every pair of nodes that is consistent with its edges. Path
+/// consistency alone may accept diagrams without one, so this backtracks over
+/// the atoms of each pair, refining the completed diagram after every choice.
+pub fn scenario(diagram: &Diagram) -> Result<Option<Diagram>, TooLarge> {
+  if diagram.nodes.len() > MAX_NODES {
+    return Err(TooLarge { nodes: diagram.nodes.len() });
+  }
+  let mut diagram = diagram.clone();
+  let nodes = diagram.nodes.keys().collect::<Vec<_>>();
+  for (i, &a) in nodes.iter().enumerate() {
+    for &b in &nodes[i + 1..] {
+      diagram.graph.insert(a, b, Arrow(0b11111));
+    }
+  }
+  diagram.complete();
+  Ok(search(diagram))
+}
+
+fn search(diagram: Diagram) -> Option<Diagram> {
+  if diagram.is_contradictory() {
+    return None;
+  }
+  let next = diagram.graph.nodes.iter().find_map(|(a, node)| {
+    node.edges.iter().find(|(&b, x)| a < b && x.0.count_ones() > 1).map(|(&b, &x)| (a, b, x))
+  });
+  let Some((a, b, x)) = next else {
+    return Some(diagram);
+  };
+  (0..5).filter(|i| x.0 & 1 << i != 0).find_map(|i| {
+    let mut diagram = diagram.clone();
+    diagram.refine(a, b, Arrow(1 << i));
+    search(diagram)
+  })
+}
+
+#[cfg(test)]
+fn item_scenario(src: &str) -> Result<Option<Diagram>, TooLarge> {
+  let system = crate::parser::SimplicityParser::parse(src).unwrap();
+  scenario(&crate::check::Item::Net(0).diagram(&system))
+}
+
+#[test]
+fn finds_an_atomic_scenario() {
+  let src = "
+    agent Con(*, {*, *})
+    agent Era(*)
+    net n(r) { Con(r, a, b) Era(a) Era(b) }
+  ";
+  let scenario = item_scenario(src).unwrap().unwrap();
+  assert!(scenario.is_complete());
+  assert!(scenario.graph.nodes.values().all(|x| x.edges.values().all(|x| x.0.count_ones() == 1)));
+  let pairs = scenario.nodes.len() * (scenario.nodes.len() - 1);
+  assert_eq!(scenario.graph.nodes.values().map(|x| x.edges.len()).sum::<usize>(), pairs);
+}
+
+#[test]
+fn finds_no_scenario_for_a_contradiction() {
+  let src = "
+    agent Con(*, *, *)
+    net n() { Con(r, a, b) Con(r, x, y) Con(a, x, z) Con(b, y, z) }
+  ";
+  assert!(item_scenario(src).unwrap().is_none());
+}
+
+#[test]
+fn refuses_large_diagrams() {
+  // Each variable joins two ports through three nodes of its own.
+  let net = |vars: usize| {
+    let nodes = (0..vars).map(|i| format!("Era(x{i}) Era(x{i}) ")).collect::<String>();
+    format!("agent Era(*)\nnet n() {{ {nodes} }}")
+  };
+  assert!(item_scenario(&net(12)).unwrap().is_some());
+  let error = item_scenario(&net(13)).unwrap_err();
+  assert_eq!(error, TooLarge { nodes: 65 });
+  assert_eq!(error.to_string(), "too large: 65 nodes, but at most 64 are searched");
+}
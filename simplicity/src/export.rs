@@ -0,0 +1,217 @@
+use std::fmt::Write;
+
+use crate::{arrow::Arrow, diagram::Diagram};
+
+/// The existence of a scenario for a diagram as a propositional formula in
+/// conjunctive normal form. Each pair of nodes `a < b` has one variable for
+/// each of the five atoms, true when that atom relates `a` to `b`.
+pub struct Problem {
+  pub nodes: usize,
+  pub clauses: Vec<Vec<i32>>,
+}
+
+impl Problem {
+  pub fn encode(diagram: &Diagram) -> Self {
+    let mut problem = Problem { nodes: diagram.nodes.len(), clauses: Vec::new() };
+    let n = problem.nodes;
+
+    for a in 0..n {
+      for b in a + 1..n {
+        problem.clauses.push((0..5).map(|k| problem.atom(a, b, k)).collect());
+        for i in 0..5 {
+          for j in i + 1..5 {
+            problem.clauses.push(vec![-problem.atom(a, b, i), -problem.atom(a, b, j)]);
+          }
+        }
+      }
+    }
+
+    for (a, node) in diagram.graph.nodes.iter() {
+      for (&b, &arrow) in &node.edges {
+        for k in (0..5).filter(|k| arrow.0 & 1 << k == 0) {
+          problem.clauses.push(vec![-problem.atom(a.0, b.0, k)]);
+        }
+      }
+    }
+
+    for a in 0..n {
+      for b in (0..n).filter(|&b| b != a) {
+        for c in (0..n).filter(|&c| c != a && c != b) {
+          for i in 0..5 {
+            for j in 0..5 {
+              let Some(ac) = Arrow(1 << i).join(Arrow(1 << j)) else { continue };
+              let mut clause = vec![-problem.atom(a, b, i), -problem.atom(b, c, j)];
+              clause.extend((0..5).filter(|k| ac.0 & 1 << k != 0).map(|k| problem.atom(a, c, k)));
+              problem.clauses.push(clause);
+            }
+          }
+        }
+      }
+    }
+
+    problem
+  }
+
+  /// The variable stating that atom `k`, the bit `1 << k` of an `Arrow`,
+  /// relates `a` to `b`.
+  fn atom(&self, a: usize, b: usize, k: usize) -> i32 {
+    if a > b {
+      return self.atom(b, a, 4 - k);
+    }
+    let pair = a * self.nodes - a * (a + 1) / 2 + (b - a - 1);
+    (pair * 5 + k + 1) as i32
+  }
+
+  fn variables(&self) -> usize {
+    self.nodes * self.nodes.saturating_sub(1) / 2 * 5
+  }
+
+  fn name(&self, var: i32) -> String {
+    let index = var.unsigned_abs() as usize - 1;
+    let (mut pair, k) = (index / 5, index % 5);
+    let mut a = 0;
+    while pair >= self.nodes - a - 1 {
+      pair -= self.nodes - a - 1;
+      a += 1;
+    }
+    format!("r_{}_{}_{}", a, a + 1 + pair, k)
+  }
+
+  pub fn dimacs(&self, title: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "c {title}").unwrap();
+    writeln!(out, "c variable 5 * pair + k + 1: atom k relates the pair's nodes").unwrap();
+    writeln!(out, "p cnf {} {}", self.variables(), self.clauses.len()).unwrap();
+    for clause in &self.clauses {
+      for literal in clause {
+        write!(out, "{literal} ").unwrap();
+      }
+      writeln!(out, "0").unwrap();
+    }
+    out
+  }
+
+  pub fn smt(&self, title: &str) -> String {
+    let mut out = String::new();
+    writeln!(out, "; {title}").unwrap();
+    writeln!(out, "; r_a_b_k: atom k, the bit 1 << k of an arrow, relates node a to node b")
+      .unwrap();
+    writeln!(out, "(set-logic QF_UF)").unwrap();
+    for var in 1..=self.variables() as i32 {
+      writeln!(out, "(declare-const {} Bool)", self.name(var)).unwrap();
+    }
+    for clause in &self.clauses {
+      let literals = clause
+        .iter()
+        .map(|&x| if x > 0 { self.name(x) } else { format!("(not {})", self.name(x)) })
+        .collect::<Vec<_>>();
+      match &literals[..] {
+        [] => writeln!(out, "(assert false)").unwrap(),
+        [literal] => writeln!(out, "(assert {literal})").unwrap(),
+        _ => writeln!(out, "(assert (or {}))", literals.join(" ")).unwrap(),
+      }
+    }
+    writeln!(out, "(check-sat)").unwrap();
+    out
+  }
+}
+
+/// Reads the answer from the output of an SMT or SAT solver: `Some(true)` if
+/// it found the problem satisfiable.
+pub fn read_result(output: &str) -> Option<bool> {
+  output.lines().find_map(|line| match line.trim() {
+    "sat" | "SAT" | "s SATISFIABLE" | "SATISFIABLE" => Some(true),
+    "unsat" | "UNSAT" | "s UNSATISFIABLE" | "UNSATISFIABLE" => Some(false),
+    _ => None,
+  })
+}
+
+/// A file name for the problem of the item called `name`.
+pub fn file_name(name: &str) -> String {
+  name.chars().map(|c| if c.is_ascii_alphanumeric() { c } else { '_' }).collect()
+}
+
+#[cfg(test)]
+fn diagram(nodes: usize, edges: &[(usize, usize, Arrow)]) -> Diagram {
+  use crate::diagram::{NodeId, NodeType};
+  let mut diagram = Diagram {
+    nodes: util::idx::IndexVec::from(vec![NodeType::Principal; nodes]),
+    ..Default::default()
+  };
+  for &(a, b, arrow) in edges {
+    diagram.graph.insert(NodeId(a), NodeId(b), arrow);
+  }
+  diagram
+}
+
+#[test]
+fn numbers_each_atom_once() {
+  let problem = Problem { nodes: 4, clauses: Vec::new() };
+  let mut atoms = Vec::new();
+  for a in 0..4 {
+    for b in a + 1..4 {
+      for k in 0..5 {
+        let atom = problem.atom(a, b, k);
+        assert_eq!(problem.atom(b, a, 4 - k), atom);
+        assert_eq!(problem.name(atom), format!("r_{a}_{b}_{k}"));
+        atoms.push(atom);
+      }
+    }
+  }
+  assert_eq!(atoms, (1..=problem.variables() as i32).collect::<Vec<_>>());
+}
+
+/// Every diagram of three nodes with edges among a few arrows is satisfiable
+/// exactly when it has a scenario, trying every assignment of atoms.
+#[test]
+fn encoding_agrees_with_exact_search() {
+  let arrows = [0b00001, 0b00100, 0b10000, 0b00011, 0b01100, 0b10001, 0b11111].map(Arrow);
+  let mut outcomes = [0; 2];
+  for x in arrows {
+    for y in arrows {
+      for z in arrows {
+        let diagram = diagram(3, &[(0, 1, x), (1, 2, y), (0, 2, z)]);
+        let problem = Problem::encode(&diagram);
+        let satisfiable = (0..125).any(|i: usize| {
+          let atoms = [i % 5, i / 5 % 5, i / 25];
+          let pairs = [(0, 1), (0, 2), (1, 2)];
+          let truth = |literal: i32| {
+            let holds =
+              pairs.iter().zip(atoms).any(|(&(a, b), k)| problem.atom(a, b, k) == literal.abs());
+            holds == (literal > 0)
+          };
+          problem.clauses.iter().all(|clause| clause.iter().any(|&x| truth(x)))
+        });
+        let scenario = crate::exact::scenario(&diagram).unwrap();
+        assert_eq!(satisfiable, scenario.is_some(), "{x:?} {y:?} {z:?}");
+        outcomes[satisfiable as usize] += 1;
+      }
+    }
+  }
+  assert!(outcomes.iter().all(|&x| x > 0));
+}
+
+#[test]
+fn writes_dimacs_and_smt() {
+  let problem = Problem::encode(&diagram(2, &[(0, 1, Arrow(0b00110))]));
+  let dimacs = problem.dimacs("net n");
+  let lines = dimacs.lines().collect::<Vec<_>>();
+  assert_eq!(lines[0], "c net n");
+  assert_eq!(lines[2], format!("p cnf 5 {}", problem.clauses.len()));
+  assert_eq!(lines[3], "1 2 3 4 5 0");
+  assert!(lines.contains(&"-1 0") && lines.contains(&"-4 0") && !lines.contains(&"-2 0"));
+
+  let smt = problem.smt("net n");
+  assert!(smt.contains("(declare-const r_0_1_4 Bool)"));
+  assert!(smt.contains("(assert (or r_0_1_0 r_0_1_1 r_0_1_2 r_0_1_3 r_0_1_4))"));
+  assert!(smt.contains("(assert (not r_0_1_0))"));
+  assert!(smt.ends_with("(check-sat)\n"));
+}
+
+#[test]
+fn reads_solver_results() {
+  assert_eq!(read_result("sat\n(model)"), Some(true));
+  assert_eq!(read_result("c comment\ns UNSATISFIABLE\n"), Some(false));
+  assert_eq!(read_result("unknown"), None);
+  assert_eq!(file_name("rule Con/Dup"), "rule_Con_Dup");
+}
@@ -0,0 +1,142 @@
+use util::idx::IndexVec;
+
+use crate::{
+  ast::{Agent, AgentDef, System, Verdict},
+  check::Item,
+  suggest::partition,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Mode {
+  Coarsest,
+  Finest,
+}
+
+/// Searches every assignment of partitions to the agents of `system` under
+/// which all of its rules and nets are simple, and returns the coarsest (or
+/// finest) of them.
+pub fn infer(system: &System, mode: Mode) -> Vec<IndexVec<Agent, AgentDef>> {
+  let ports = system.agents.defs.values().map(|def| def.value.arity() - 1).collect::<Vec<usize>>();
+
+  // Each item is checked as soon as every agent it uses has been assigned.
+  let mut items_at = vec![Vec::new(); ports.len()];
+  for item in Item::all(system) {
+    match item.agents(system).into_iter().max() {
+      Some(last) => items_at[last.0].push(item),
+      None if item.check(system) == Verdict::NonSimple => return Vec::new(),
+      None => {}
+    }
+  }
+
+  let mut infer =
+    Infer { system: system.clone(), ports, items_at, breaks: Vec::new(), valid: Vec::new() };
+  infer.search();
+
+  let valid = &infer.valid;
+  let finer = |a: &Vec<usize>, b: &Vec<usize>| a.iter().zip(b).all(|(a, b)| a & b == *b);
+  let extremal = valid.iter().filter(|a| {
+    !valid.iter().any(|b| {
+      a != &b
+        && match mode {
+          Mode::Coarsest => finer(a, b),
+          Mode::Finest => finer(b, a),
+        }
+    })
+  });
+  extremal
+    .map(|breaks| {
+      breaks
+        .iter()
+        .zip(&infer.ports)
+        .zip(system.agents.defs.values())
+        .map(|((&breaks, &ports), def)| def.value.regroup(&partition(ports, breaks)))
+        .collect::<Vec<_>>()
+        .into()
+    })
+    .collect()
+}
+
+struct Infer {
+  system: System,
+  ports: Vec<usize>,
+  items_at: Vec<Vec<Item>>,
+  breaks: Vec<usize>,
+  valid: Vec<Vec<usize>>,
+}
+
+impl Infer {
+  fn search(&mut self) {
+    let agent = self.breaks.len();
+    if agent == self.ports.len() {
+      self.valid.push(self.breaks.clone());
+      return;
+    }
+    let ports = self.ports[agent];
+    for breaks in 0..1usize << ports.saturating_sub(1) {
+      let def = &mut self.system.agents.defs[Agent(agent)].value;
+      *def = def.regroup(&partition(ports, breaks));
+      if self.items_at[agent].iter().all(|x| x.check(&self.system) == Verdict::Simple) {
+        self.breaks.push(breaks);
+        self.search();
+        self.breaks.pop();
+      }
+    }
+  }
+}
+
+#[test]
+fn infers_the_only_simple_assignment() {
+  use crate::parser::SimplicityParser;
+
+  let rule = "
+    agent Con(*, *, *)
+    agent Dup(*, *, *)
+    rule Con(_, a, b) Dup(_, c, d) {
+      Dup(a, x, y)
+      Dup(b, z, w)
+      Con(c, x, z)
+      Con(d, y, w)
+    }
+  ";
+  let show = |system: &System, assignments: Vec<IndexVec<Agent, AgentDef>>| {
+    let show = |(agent, def): (Agent, &AgentDef)| def.show(&system.agents.defs[agent].name);
+    assignments.iter().map(|x| x.iter().map(show).collect::<Vec<_>>()).collect::<Vec<_>>()
+  };
+
+  let system = SimplicityParser::parse(rule).unwrap();
+  let either = [["Con(*, {*, *})", "Dup(*, *, *)"], ["Con(*, *, *)", "Dup(*, {*, *})"]];
+  assert_eq!(show(&system, infer(&system, Mode::Coarsest)), either);
+  assert_eq!(show(&system, infer(&system, Mode::Finest)), either);
+
+  // This net rules out grouping the ports of `Dup` alone.
+  let net = "
+    net n(r) {
+      Con(r, a, b)
+      Con(a, x, y)
+      Dup(b, x, y)
+    }
+  ";
+  let system = SimplicityParser::parse(&format!("{rule}{net}")).unwrap();
+  let only = [["Con(*, {*, *})", "Dup(*, *, *)"]];
+  assert_eq!(show(&system, infer(&system, Mode::Coarsest)), only);
+  assert_eq!(show(&system, infer(&system, Mode::Finest)), only);
+}
+
+#[test]
+fn infers_nothing_when_no_assignment_works() {
+  use crate::parser::SimplicityParser;
+
+  let system = SimplicityParser::parse(
+    "
+    agent Dup(*, *, *)
+    rule Dup(_, a, b) Dup(_, c, d) {
+      Dup(a, x, y)
+      Dup(b, z, w)
+      Dup(c, x, z)
+      Dup(d, y, w)
+    }
+    ",
+  )
+  .unwrap();
+  assert!(infer(&system, Mode::Coarsest).is_empty());
+}
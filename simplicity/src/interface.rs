@@ -0,0 +1,78 @@
+use crate::{
+  arrow::Arrow,
+  ast::{Partition, Var},
+  diagram::Diagram,
+};
+
+/// The derived relation between every pair of free ports of a completed
+/// diagram; pairs with no derived edge are unconstrained.
+pub fn relation(diagram: &Diagram) -> Vec<Vec<Arrow>> {
+  let ports = &diagram.free_ports;
+  ports
+    .iter()
+    .map(|&a| {
+      ports.iter().map(|&b| diagram.graph.get_edge(a, b).unwrap_or(Arrow(0b11111))).collect()
+    })
+    .collect()
+}
+
+/// Finds a grouping of the ports that, inserted as free ports, induces exactly
+/// `relation`, returning the groups as lists of port indices.
+pub fn partition(relation: &[Vec<Arrow>]) -> Option<Vec<Vec<usize>>> {
+  let mut group = vec![None; relation.len()];
+  let mut groups = Vec::<Vec<usize>>::new();
+  for i in 0..relation.len() {
+    let g = *group[i].get_or_insert_with(|| {
+      groups.push(vec![i]);
+      groups.len() - 1
+    });
+    for j in i + 1..relation.len() {
+      if relation[i][j] != Arrow(0b11111) && group[j].is_none() {
+        group[j] = Some(g);
+        groups[g].push(j);
+      }
+    }
+  }
+
+  let order = groups.iter().flatten().copied().collect::<Vec<_>>();
+  let mut diagram = Diagram::default();
+  let partition = groups
+    .iter()
+    .map(|group| Partition::Group(group.iter().map(Partition::Port).collect()))
+    .collect::<Vec<_>>();
+  diagram.insert_free_ports((0..order.len()).map(Var), &partition);
+  diagram.complete();
+  let induced = self::relation(&diagram);
+  for (i, &a) in order.iter().enumerate() {
+    for (j, &b) in order.iter().enumerate() {
+      if i != j && induced[i][j] != relation[a][b] {
+        return None;
+      }
+    }
+  }
+  Some(groups)
+}
+
+#[test]
+fn partition_recovers_grouping() {
+  let (port, pair) = (Partition::Port(()), Partition::Group(vec![Partition::Port(()); 2]));
+  let cases = [
+    (vec![port.clone(), port.clone()], vec![vec![0], vec![1]]),
+    (vec![pair.clone()], vec![vec![0, 1]]),
+    (vec![port, pair], vec![vec![0], vec![1, 2]]),
+  ];
+  for (ports, groups) in cases {
+    let count = ports.iter().map(Partition::count).sum();
+    let mut diagram = Diagram::default();
+    diagram.insert_free_ports((0..count).map(Var), &ports);
+    diagram.complete();
+    assert_eq!(partition(&relation(&diagram)), Some(groups));
+  }
+}
+
+#[test]
+fn partition_rejects_relation_no_grouping_induces() {
+  let any = Arrow(0b11111);
+  let relation = [vec![any, Arrow(0b01000)], vec![Arrow(0b00010), any]];
+  assert_eq!(partition(&relation), None);
+}
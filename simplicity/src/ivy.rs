@@ -0,0 +1,312 @@
+use std::{collections::HashMap, mem::transmute};
+
+use logos::Logos;
+
+use util::{
+  idx::IndexVec,
+  lexer::{lex_block_comment, Token as TokenTrait, TokenSet},
+  parser::{Delimiters, Parser, ParserState},
+};
+
+use crate::{
+  ast::{Agent, AgentDef, NetDef, Node, Partition, System, Var},
+  parser::SimplicityParser,
+  scope::{Scope, ScopeBuilder},
+};
+
+#[derive(Logos, Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[logos(skip r"[ \t\n\f]+")]
+#[repr(u8)]
+pub enum IvyToken {
+  #[token("(")]
+  OpenParen,
+  #[token(")")]
+  CloseParen,
+  #[token("{")]
+  OpenBrace,
+  #[token("}")]
+  CloseBrace,
+  #[token("=")]
+  Eq,
+  #[token("_")]
+  Hole,
+  #[token("?")]
+  Question,
+
+  #[regex(r"::[\p{ID_Continue}:.]+")]
+  Global,
+  #[regex(r"@[\p{ID_Continue}:.]+\$?")]
+  ExtFn,
+  #[regex(r"[+-]?[0-9][\p{ID_Continue}.]*")]
+  Num,
+  #[regex(r"\p{ID_Start}[\p{ID_Continue}.]*|_[\p{ID_Continue}.]+")]
+  Ident,
+
+  #[regex("//.*", logos::skip)]
+  #[token("/*", lex_block_comment)]
+  Skip,
+}
+
+impl TokenTrait for IvyToken {
+  fn into_u8(self) -> u8 {
+    self as u8
+  }
+
+  unsafe fn from_u8(value: u8) -> Self {
+    unsafe { transmute::<u8, IvyToken>(value) }
+  }
+}
+
+/// Reads the nets of an Ivy file, such as the Vine compiler emits, into a
+/// system without rules. Every kind of node becomes an agent: combinators
+/// under their label, extrinsic functions under their name (with a trailing
+/// `$` when their arguments are swapped), and `_` erasers, `#` numbers, `::`
+/// global references and `?` branches. Agents take their partition from
+/// `partitions` where given, and otherwise keep every auxiliary port in a
+/// group of its own.
+pub struct IvyParser<'src> {
+  pub state: ParserState<'src, IvyToken>,
+  partitions: &'src HashMap<String, AgentDef>,
+  agents: ScopeBuilder<'src, Agent, AgentDef>,
+  vars: ScopeBuilder<'src, Var, ()>,
+  nodes: Vec<Node>,
+  wires: Vec<(Var, Var)>,
+  nets: Vec<NetDef>,
+}
+
+#[derive(Debug, Clone)]
+pub enum IvyError<'src> {
+  LexError,
+  UnexpectedToken { expected: TokenSet<IvyToken>, found: &'src str },
+  WrongArity { agent: String, expected: usize, found: usize },
+}
+
+type Parse<'src, T = ()> = Result<T, IvyError<'src>>;
+
+impl<'src> Parser<'src> for IvyParser<'src> {
+  type Token = IvyToken;
+  type Error = IvyError<'src>;
+
+  fn state(&mut self) -> &mut ParserState<'src, Self::Token> {
+    &mut self.state
+  }
+
+  fn lex_error(&self) -> Self::Error {
+    IvyError::LexError
+  }
+
+  fn unexpected_error(&self) -> IvyError<'src> {
+    IvyError::UnexpectedToken { expected: self.state.expected, found: self.state.lexer.slice() }
+  }
+}
+
+impl<'src> IvyParser<'src> {
+  pub fn parse(src: &'src str, partitions: &'src HashMap<String, AgentDef>) -> Parse<'src, System> {
+    let mut parser = IvyParser {
+      state: ParserState::new(src),
+      partitions,
+      agents: ScopeBuilder::default(),
+      vars: ScopeBuilder::default(),
+      nodes: Vec::new(),
+      wires: Vec::new(),
+      nets: Vec::new(),
+    };
+    parser.bump()?;
+    while parser.state.token.is_some() {
+      parser.parse_net_def()?;
+    }
+    Ok(System { agents: parser.agents.finish(), rules: Vec::new(), nets: parser.nets })
+  }
+
+  fn parse_net_def(&mut self) -> Parse<'src> {
+    let name = self.expect(IvyToken::Global)?;
+    self.expect(IvyToken::OpenBrace)?;
+    let root = self.parse_tree()?;
+    while !self.eat(IvyToken::CloseBrace)? {
+      let a = self.parse_tree()?;
+      self.expect(IvyToken::Eq)?;
+      let b = self.parse_tree()?;
+      self.wires.push((a, b));
+    }
+    let net = self.finish_net(name.trim_start_matches("::").to_owned(), root);
+    self.nets.push(net);
+    Ok(())
+  }
+
+  /// Parses a tree, adding its nodes to the current net, and returns the
+  /// variable connected to its root.
+  fn parse_tree(&mut self) -> Parse<'src, Var> {
+    if self.check(IvyToken::Ident) {
+      let name = self.expect(IvyToken::Ident)?;
+      if self.check(IvyToken::OpenParen) {
+        self.parse_node(name)
+      } else {
+        Ok(self.vars.get_or_define(name, ()))
+      }
+    } else if self.eat(IvyToken::Hole)? {
+      self.add_node("_", Vec::new())
+    } else if self.eat(IvyToken::Num)? {
+      self.add_node("#", Vec::new())
+    } else if self.eat(IvyToken::Global)? {
+      self.add_node("::", Vec::new())
+    } else if self.check(IvyToken::ExtFn) {
+      let name = self.expect(IvyToken::ExtFn)?;
+      self.parse_node(name)
+    } else if self.check(IvyToken::Question) {
+      let name = self.expect(IvyToken::Question)?;
+      self.parse_node(name)
+    } else {
+      Err(self.unexpected_error())
+    }
+  }
+
+  fn parse_node(&mut self, name: &'src str) -> Parse<'src, Var> {
+    let children = self.parse_delimited(
+      Delimiters {
+        open: Some(IvyToken::OpenParen),
+        close: Some(IvyToken::CloseParen),
+        separator: None,
+      },
+      Self::parse_tree,
+    )?;
+    self.add_node(name, children)
+  }
+
+  fn add_node(&mut self, name: &'src str, children: Vec<Var>) -> Parse<'src, Var> {
+    let agent = match self.agents.get(name) {
+      Some(agent) => agent,
+      None => {
+        let def = match self.partitions.get(name) {
+          Some(def) => def.clone(),
+          None => AgentDef {
+            auxiliary: children.iter().map(|_| Partition::Port(None)).collect(),
+            axioms: Vec::new(),
+            allow: Vec::new(),
+          },
+        };
+        self.agents.define(name, def).unwrap()
+      }
+    };
+    let expected = self.agents.scope.defs[agent].value.arity();
+    if children.len() + 1 != expected {
+      return Err(IvyError::WrongArity {
+        agent: name.to_owned(),
+        expected,
+        found: children.len() + 1,
+      });
+    }
+    let principal = self.vars.scope.define(format!("~{}", self.vars.scope.defs.len()), ());
+    let mut ports = vec![principal];
+    ports.extend(children);
+    self.nodes.push(Node { agent, ports, span: None });
+    Ok(principal)
+  }
+
+  /// Builds a net from the nodes parsed since the last one, merging the
+  /// variables joined by wires, with `root` as its only free port.
+  fn finish_net(&mut self, name: String, root: Var) -> NetDef {
+    let old = self.vars.finish();
+    let mut parent = IndexVec::<Var, Var>::from(old.defs.keys().collect::<Vec<_>>());
+    fn find(parent: &mut IndexVec<Var, Var>, var: Var) -> Var {
+      if parent[var] == var {
+        var
+      } else {
+        let root = find(parent, parent[var]);
+        parent[var] = root;
+        root
+      }
+    }
+    for (a, b) in self.wires.drain(..) {
+      let (a, b) = (find(&mut parent, a), find(&mut parent, b));
+      if a == b {
+        continue;
+      }
+      // Prefer to keep the names that appear in the source.
+      if old.defs[a].name.starts_with('~') {
+        parent[a] = b;
+      } else {
+        parent[b] = a;
+      }
+    }
+
+    let mut vars = Scope::default();
+    let mut renamed = HashMap::new();
+    let mut rename = |var: Var| {
+      let var = find(&mut parent, var);
+      *renamed.entry(var).or_insert_with(|| vars.define(old.defs[var].name.clone(), ()))
+    };
+    let root = rename(root);
+    let ports = vec![Partition::Port(root)];
+    let mut nodes = std::mem::take(&mut self.nodes);
+    for node in &mut nodes {
+      for port in &mut node.ports {
+        *port = rename(*port);
+      }
+    }
+    if vars.defs[root].name.starts_with('~') {
+      vars.defs[root].name = "root".into();
+    }
+    NetDef { name, vars, ports, nodes, constraints: Vec::new(), expect: None, allow: Vec::new() }
+  }
+}
+
+/// Parses the partition of an agent's auxiliary ports written as in an agent
+/// definition, such as `*, {*, *}`.
+pub fn parse_partition(spec: &str) -> Option<AgentDef> {
+  let src =
+    if spec.trim().is_empty() { "agent X(*)".into() } else { format!("agent X(*, {spec})") };
+  let system = SimplicityParser::parse(&src).ok()?;
+  Some(system.agents.defs.into_values().next()?.value)
+}
+
+#[test]
+fn reads_nested_trees() {
+  let partitions = HashMap::new();
+  let src = "::main { fn(x fn(_ y)) x = @add(y 1) }";
+  let system = IvyParser::parse(src, &partitions).unwrap();
+  let names = system.agents.defs.values().map(|x| &*x.name).collect::<Vec<_>>();
+  assert_eq!(names, ["_", "fn", "#", "@add"]);
+  let net = &system.nets[0];
+  assert_eq!(net.name, "main");
+  let show = |node: &Node| {
+    let ports = node.ports.iter().map(|&v| &*net.vars.defs[v].name).collect::<Vec<_>>();
+    format!("{}({})", system.agents.defs[node.agent].name, ports.join(", "))
+  };
+  let nodes = net.nodes.iter().map(show).collect::<Vec<_>>();
+  assert_eq!(nodes, ["_(~1)", "fn(~3, ~1, y)", "fn(root, x, ~3)", "#(~5)", "@add(x, y, ~5)"]);
+  assert!(matches!(net.ports[..], [Partition::Port(root)] if net.vars.defs[root].name == "root"));
+}
+
+/// An imported net has the verdict of the same net written by hand.
+#[test]
+fn checks_imported_nets() {
+  use crate::ast::Verdict;
+  let src = "::main { r  r = fn(a b)  a = @add(b 1) }";
+  let check = |spec| {
+    let partitions = HashMap::from([("fn".to_owned(), parse_partition(spec).unwrap())]);
+    let system = IvyParser::parse(src, &partitions).unwrap();
+    crate::check::Item::Net(0).check(&system)
+  };
+  assert_eq!(check("*, *"), Verdict::NonSimple);
+  assert_eq!(check("{*, *}"), Verdict::Simple);
+
+  let nets = |fn_ports| {
+    let src = format!(
+      "agent Fn(*, {fn_ports}) agent Add(*, *, *) agent Num(*)
+      net main(r) {{ Fn(r, a, b) Add(a, b, n) Num(n) }}"
+    );
+    crate::check::Item::Net(0).check(&SimplicityParser::parse(&src).unwrap())
+  };
+  assert_eq!(nets("*, *"), Verdict::NonSimple);
+  assert_eq!(nets("{*, *}"), Verdict::Simple);
+}
+
+#[test]
+fn rejects_partitions_of_the_wrong_arity() {
+  let partitions = HashMap::from([("fn".to_owned(), parse_partition("*").unwrap())]);
+  let Err(error) = IvyParser::parse("::main { fn(x x) }", &partitions) else { panic!() };
+  assert!(matches!(
+    error,
+    IvyError::WrongArity { agent, expected: 2, found: 3 } if agent == "fn"
+  ));
+}
@@ -0,0 +1,389 @@
+use std::fmt::{self, Display, Write};
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Json {
+  Null,
+  Bool(bool),
+  Number(f64),
+  String(String),
+  Array(Vec<Json>),
+  Object(Vec<(String, Json)>),
+}
+
+impl Json {
+  pub fn object<'a>(entries: impl IntoIterator<Item = (&'a str, Json)>) -> Self {
+    Json::Object(entries.into_iter().map(|(k, v)| (k.to_owned(), v)).collect())
+  }
+
+  pub fn get(&self, key: &str) -> Option<&Json> {
+    match self {
+      Json::Object(entries) => entries.iter().find(|(k, _)| k == key).map(|(_, v)| v),
+      _ => None,
+    }
+  }
+
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Json::String(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  /// The number, if it is a whole number that fits in a `u32`.
+  pub fn as_u32(&self) -> Option<u32> {
+    match *self {
+      Json::Number(n) if n.fract() == 0.0 && (0.0..=u32::MAX as f64).contains(&n) => Some(n as u32),
+      _ => None,
+    }
+  }
+
+  pub fn as_bool(&self) -> Option<bool> {
+    match self {
+      Json::Bool(b) => Some(*b),
+      _ => None,
+    }
+  }
+
+  pub fn as_array(&self) -> Option<&[Json]> {
+    match self {
+      Json::Array(items) => Some(items),
+      _ => None,
+    }
+  }
+
+  /// Parses a JSON document, failing on malformed input and on arrays and
+  /// objects nested more than [`MAX_DEPTH`] deep.
+  pub fn parse(src: &str) -> Option<Json> {
+    let mut parser = JsonParser { src: src.as_bytes(), i: 0, depth: 0 };
+    let value = parser.parse_value()?;
+    parser.skip_whitespace();
+    (parser.i == parser.src.len()).then_some(value)
+  }
+}
+
+impl From<&str> for Json {
+  fn from(value: &str) -> Self {
+    Json::String(value.to_owned())
+  }
+}
+
+impl From<String> for Json {
+  fn from(value: String) -> Self {
+    Json::String(value)
+  }
+}
+
+impl From<usize> for Json {
+  fn from(value: usize) -> Self {
+    Json::Number(value as f64)
+  }
+}
+
+impl From<u32> for Json {
+  fn from(value: u32) -> Self {
+    Json::Number(value as f64)
+  }
+}
+
+impl From<bool> for Json {
+  fn from(value: bool) -> Self {
+    Json::Bool(value)
+  }
+}
+
+impl From<Vec<Json>> for Json {
+  fn from(value: Vec<Json>) -> Self {
+    Json::Array(value)
+  }
+}
+
+impl Display for Json {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    match self {
+      Json::Null => f.write_str("null"),
+      Json::Bool(b) => write!(f, "{b}"),
+      Json::Number(n) if n.fract() == 0.0 && n.abs() < 1e15 => write!(f, "{}", *n as i64),
+      Json::Number(n) => write!(f, "{n}"),
+      Json::String(s) => write_string(f, s),
+      Json::Array(items) => {
+        f.write_char('[')?;
+        for (i, item) in items.iter().enumerate() {
+          if i != 0 {
+            f.write_char(',')?;
+          }
+          write!(f, "{item}")?;
+        }
+        f.write_char(']')
+      }
+      Json::Object(entries) => {
+        f.write_char('{')?;
+        for (i, (key, value)) in entries.iter().enumerate() {
+          if i != 0 {
+            f.write_char(',')?;
+          }
+          write_string(f, key)?;
+          write!(f, ":{value}")?;
+        }
+        f.write_char('}')
+      }
+    }
+  }
+}
+
+fn write_string(f: &mut fmt::Formatter<'_>, s: &str) -> fmt::Result {
+  f.write_char('"')?;
+  for c in s.chars() {
+    match c {
+      '"' => f.write_str("\\\"")?,
+      '\\' => f.write_str("\\\\")?,
+      '\n' => f.write_str("\\n")?,
+      '\r' => f.write_str("\\r")?,
+      '\t' => f.write_str("\\t")?,
+      c if (c as u32) < 0x20 => write!(f, "\\u{:04x}", c as u32)?,
+      c => f.write_char(c)?,
+    }
+  }
+  f.write_char('"')
+}
+
+/// How deep arrays and objects may nest, so that hostile input cannot
+/// exhaust the stack.
+pub const MAX_DEPTH: usize = 128;
+
+struct JsonParser<'a> {
+  src: &'a [u8],
+  i: usize,
+  /// The number of arrays and objects around the value being parsed.
+  depth: usize,
+}
+
+impl JsonParser<'_> {
+  fn skip_whitespace(&mut self) {
+    while self.src.get(self.i).is_some_and(|c| c.is_ascii_whitespace()) {
+      self.i += 1;
+    }
+  }
+
+  fn eat(&mut self, c: u8) -> bool {
+    self.skip_whitespace();
+    if self.src.get(self.i) == Some(&c) {
+      self.i += 1;
+      true
+    } else {
+      false
+    }
+  }
+
+  fn eat_keyword(&mut self, keyword: &str) -> bool {
+    if self.src[self.i..].starts_with(keyword.as_bytes()) {
+      self.i += keyword.len();
+      true
+    } else {
+      false
+    }
+  }
+
+  fn parse_value(&mut self) -> Option<Json> {
+    self.skip_whitespace();
+    match *self.src.get(self.i)? {
+      b'n' if self.eat_keyword("null") => Some(Json::Null),
+      b't' if self.eat_keyword("true") => Some(Json::Bool(true)),
+      b'f' if self.eat_keyword("false") => Some(Json::Bool(false)),
+      b'"' => self.parse_string().map(Json::String),
+      b'[' => {
+        self.enter()?;
+        let mut items = Vec::new();
+        if !self.eat(b']') {
+          loop {
+            items.push(self.parse_value()?);
+            if self.eat(b']') {
+              break;
+            }
+            if !self.eat(b',') {
+              return None;
+            }
+          }
+        }
+        self.depth -= 1;
+        Some(Json::Array(items))
+      }
+      b'{' => {
+        self.enter()?;
+        let mut entries = Vec::new();
+        if !self.eat(b'}') {
+          loop {
+            self.skip_whitespace();
+            let key = self.parse_string()?;
+            if !self.eat(b':') {
+              return None;
+            }
+            entries.push((key, self.parse_value()?));
+            if self.eat(b'}') {
+              break;
+            }
+            if !self.eat(b',') {
+              return None;
+            }
+          }
+        }
+        self.depth -= 1;
+        Some(Json::Object(entries))
+      }
+      _ => {
+        let start = self.i;
+        while self
+          .src
+          .get(self.i)
+          .is_some_and(|c| matches!(c, b'-' | b'+' | b'.' | b'e' | b'E' | b'0'..=b'9'))
+        {
+          self.i += 1;
+        }
+        let number = std::str::from_utf8(&self.src[start..self.i]).ok()?;
+        is_number(number).then(|| number.parse().ok().map(Json::Number))?
+      }
+    }
+  }
+
+  /// Steps into an array or object, failing if it is nested too deep.
+  fn enter(&mut self) -> Option<()> {
+    self.i += 1;
+    self.depth += 1;
+    (self.depth <= MAX_DEPTH).then_some(())
+  }
+
+  fn parse_string(&mut self) -> Option<String> {
+    if self.src.get(self.i) != Some(&b'"') {
+      return None;
+    }
+    self.i += 1;
+    let mut bytes = Vec::new();
+    loop {
+      match *self.src.get(self.i)? {
+        b'"' => {
+          self.i += 1;
+          return String::from_utf8(bytes).ok();
+        }
+        b'\\' => {
+          self.i += 1;
+          let c = match *self.src.get(self.i)? {
+            b'n' => '\n',
+            b'r' => '\r',
+            b't' => '\t',
+            b'b' => '\u{8}',
+            b'f' => '\u{c}',
+            c @ (b'"' | b'\\' | b'/') => c as char,
+            b'u' => {
+              let mut code = self.parse_hex()?;
+              if (0xd800..0xdc00).contains(&code) && self.src[self.i + 1..].starts_with(b"\\u") {
+                self.i += 2;
+                let low = self.parse_hex()?.checked_sub(0xdc00).filter(|&x| x < 0x400)?;
+                code = 0x10000 + ((code - 0xd800) << 10) + low;
+              }
+              char::from_u32(code)?
+            }
+            _ => return None,
+          };
+          self.i += 1;
+          bytes.extend_from_slice(c.encode_utf8(&mut [0; 4]).as_bytes());
+        }
+        c if c < 0x20 => return None,
+        c => {
+          self.i += 1;
+          bytes.push(c);
+        }
+      }
+    }
+  }
+
+  fn parse_hex(&mut self) -> Option<u32> {
+    let hex = self.src.get(self.i + 1..self.i + 5)?;
+    self.i += 4;
+    u32::from_str_radix(std::str::from_utf8(hex).ok()?, 16).ok()
+  }
+}
+
+/// Whether `s` is a number in JSON's syntax, which unlike Rust's has no `+`
+/// sign, no leading zeros, and digits on both sides of a decimal point.
+fn is_number(s: &str) -> bool {
+  let s = s.strip_prefix('-').unwrap_or(s);
+  let (mantissa, exponent) = match s.find(['e', 'E']) {
+    Some(i) => (&s[..i], Some(&s[i + 1..])),
+    None => (s, None),
+  };
+  let (whole, fraction) = match mantissa.split_once('.') {
+    Some((whole, fraction)) => (whole, Some(fraction)),
+    None => (mantissa, None),
+  };
+  let digits = |s: &str| !s.is_empty() && s.bytes().all(|c| c.is_ascii_digit());
+  digits(whole)
+    && (whole == "0" || !whole.starts_with('0'))
+    && fraction.is_none_or(digits)
+    && exponent.map(|x| x.strip_prefix(['+', '-']).unwrap_or(x)).is_none_or(digits)
+}
+
+#[test]
+fn round_trips() {
+  let value = Json::object([
+    ("null", Json::Null),
+    ("bools", vec![true.into(), false.into()].into()),
+    ("numbers", vec![0u32.into(), Json::Number(-12.5), Json::Number(1e20)].into()),
+    ("string", "quote \" backslash \\ newline \n tab \t bell \u{7} é 🦀".into()),
+    ("nested", Json::object([("empty", Json::Array(vec![])), ("object", Json::object([]))])),
+  ]);
+  assert_eq!(Json::parse(&value.to_string()), Some(value));
+}
+
+#[test]
+fn parses_escapes_and_whitespace() {
+  let value = Json::parse(" { \"a\" : [ 1 , \"\\u00e9\\ud83e\\udd80\\/\" ] } ").unwrap();
+  assert_eq!(value, Json::object([("a", vec![Json::Number(1.0), "é🦀/".into()].into())]));
+}
+
+#[test]
+fn rejects_malformed_input() {
+  let malformed = [
+    "",
+    "[1,]",
+    "[1 2]",
+    "{\"a\" 1}",
+    "{\"a\": 1,}",
+    "{1: 2}",
+    "[1] [2]",
+    "nul",
+    "\"unterminated",
+    "\"bad \\x escape\"",
+    "\"lone \\ud83e surrogate\"",
+    "\"bad \\ud83e\\u0041 pair\"",
+    "\"raw \n newline\"",
+    "+1",
+    "01",
+    "1.",
+    ".5",
+    "1e",
+    "--1",
+  ];
+  for src in malformed {
+    assert_eq!(Json::parse(src), None, "{src:?} should not parse");
+  }
+}
+
+#[test]
+fn limits_nesting() {
+  let nested = |depth| "[".repeat(depth) + &"]".repeat(depth);
+  assert!(Json::parse(&nested(MAX_DEPTH)).is_some());
+  assert!(Json::parse(&nested(MAX_DEPTH + 1)).is_none());
+  assert!(Json::parse(&nested(1_000_000)).is_none());
+  assert!(Json::parse(&"{\"a\":".repeat(1_000_000)).is_none());
+}
+
+#[test]
+fn converts_only_whole_numbers_to_u32() {
+  let u32 = |n: f64| Json::Number(n).as_u32();
+  assert_eq!(u32(0.0), Some(0));
+  assert_eq!(u32(42.0), Some(42));
+  assert_eq!(u32(u32::MAX as f64), Some(u32::MAX));
+  assert_eq!(u32(-1.0), None);
+  assert_eq!(u32(1.5), None);
+  assert_eq!(u32(u32::MAX as f64 + 1.0), None);
+  assert_eq!(Json::from("1").as_u32(), None);
+}
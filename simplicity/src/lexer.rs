@@ -16,6 +16,12 @@ pub enum Token {
   OpenBrace,
   #[token("}")]
   CloseBrace,
+  #[token("[")]
+  OpenBracket,
+  #[token("]")]
+  CloseBracket,
+  #[token("#")]
+  Hash,
   #[token(",")]
   Comma,
   #[token("+")]
@@ -35,9 +41,21 @@ pub enum Token {
   Rule,
   #[token("net")]
   Net,
+  #[token("assume")]
+  Assume,
+  #[token("assert")]
+  Assert,
+
+  /// The relation between two variables in an assumption or assertion, such
+  /// as `<`, `*|>>` or `--*>>`. A lone `*` or `-` is lexed as `Star` or
+  /// `Minus`.
+  #[regex(r"[<>|][<>*|-]*|[*-][<>*|-]+")]
+  Glyph,
 
   #[regex(r"[\p{ID_Start}_]\p{ID_Continue}*")]
   Ident,
+  #[regex("[0-9]+")]
+  Num,
 
   #[regex("//.*", logos::skip)]
   #[token("/*", lex_block_comment)]
@@ -0,0 +1,188 @@
+use std::{
+  collections::{HashMap, HashSet},
+  fmt::{self, Display},
+};
+
+use crate::{
+  ast::{Agent, Lint, Node, System, Var},
+  check::Item,
+};
+
+/// A problem found by a lint, in the agent, rule or net named `item`.
+#[derive(Debug, Clone)]
+pub struct Finding {
+  pub lint: Lint,
+  pub item: String,
+  pub message: String,
+}
+
+impl Display for Finding {
+  fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+    write!(f, "{}: {} [{}]", self.item, self.message, self.lint)
+  }
+}
+
+/// Runs every lint over `system`, leaving out the findings that their item
+/// allows. Agents come first, then rules and nets in order.
+pub fn lint(system: &System) -> Vec<Finding> {
+  let mut findings = Vec::new();
+
+  let used = Item::all(system).flat_map(|item| item.agents(system)).collect::<HashSet<_>>();
+  let with_rules =
+    system.rules.iter().flat_map(|rule| [rule.a.agent, rule.b.agent]).collect::<HashSet<_>>();
+  for (agent, def) in system.agents.defs.iter() {
+    let mut report = |lint, message: &str| {
+      if !def.value.allow.contains(&lint) {
+        let item = format!("agent {}", def.name);
+        findings.push(Finding { lint, item, message: message.to_owned() });
+      }
+    };
+    // An agent that is never used needs no rules, so only that is reported.
+    if !used.contains(&agent) {
+      report(Lint::UnusedAgent, "is never used");
+    } else if !with_rules.contains(&agent) {
+      report(Lint::AgentWithoutRules, "has no rules");
+    }
+  }
+
+  let bodies =
+    system.rules.iter().map(|rule| &rule.result).chain(system.nets.iter().map(|x| &x.nodes));
+  let pairs = bodies.flat_map(|nodes| active_pairs(nodes)).collect::<HashSet<_>>();
+
+  for item in Item::all(system) {
+    let allow = item.allow(system);
+    let mut report = |lint, message: String| {
+      if !allow.contains(&lint) {
+        findings.push(Finding { lint, item: item.name(system), message });
+      }
+    };
+    if let Item::Rule(i) = item {
+      let rule = &system.rules[i];
+      if !pairs.contains(&pair(rule.a.agent, rule.b.agent)) {
+        report(Lint::RuleNeverFires, "its agents never meet in any net".to_owned());
+      }
+    }
+    if let Item::Net(i) = item {
+      let parts = parts(&system.nets[i].nodes);
+      if parts > 1 {
+        report(Lint::DisconnectedNet, format!("its nodes fall into {parts} disconnected parts"));
+      }
+    }
+    let mut uses = HashMap::<Var, usize>::new();
+    for var in item.ports(system).into_iter().chain(body_ports(system, item)) {
+      *uses.entry(var).or_default() += 1;
+    }
+    let vars = item.vars(system);
+    for (var, def) in vars.defs.iter() {
+      if uses.get(&var) == Some(&1) {
+        report(Lint::SingleUseVar, format!("variable `{}` is used only once", def.name));
+      }
+    }
+  }
+
+  findings
+}
+
+/// The variables of the ports of the nodes an item adds to its free ports: the
+/// result of a rule, or every node of a net.
+fn body_ports(system: &System, item: Item) -> impl Iterator<Item = Var> + '_ {
+  let nodes = match item {
+    Item::Rule(i) => &system.rules[i].result,
+    Item::Net(i) => &system.nets[i].nodes,
+  };
+  nodes.iter().flat_map(|node| node.ports.iter().copied())
+}
+
+fn pair(a: Agent, b: Agent) -> (Agent, Agent) {
+  (a.min(b), a.max(b))
+}
+
+/// The pairs of agents of `nodes` that are connected by their principal ports.
+fn active_pairs(nodes: &[Node]) -> Vec<(Agent, Agent)> {
+  let mut principals = HashMap::<Var, Agent>::new();
+  let mut pairs = Vec::new();
+  for node in nodes {
+    let Some(&principal) = node.ports.first() else { continue };
+    if let Some(other) = principals.insert(principal, node.agent) {
+      pairs.push(pair(node.agent, other));
+    }
+  }
+  pairs
+}
+
+/// The number of connected parts into which `nodes` fall, where two nodes are
+/// connected when they share a variable.
+fn parts(nodes: &[Node]) -> usize {
+  let mut parent = (0..nodes.len()).collect::<Vec<_>>();
+  fn find(parent: &mut [usize], i: usize) -> usize {
+    if parent[i] == i {
+      i
+    } else {
+      let root = find(parent, parent[i]);
+      parent[i] = root;
+      root
+    }
+  }
+  let mut first = HashMap::<Var, usize>::new();
+  for (i, node) in nodes.iter().enumerate() {
+    for &var in &node.ports {
+      let j = *first.entry(var).or_insert(i);
+      let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+      parent[a] = b;
+    }
+  }
+  (0..nodes.len()).filter(|&i| find(&mut parent, i) == i).count()
+}
+
+#[cfg(test)]
+fn findings(src: &str) -> Vec<String> {
+  let system = crate::parser::SimplicityParser::parse(src).unwrap();
+  lint(&system).iter().map(Finding::to_string).collect()
+}
+
+#[test]
+fn finds_each_lint() {
+  let src = "
+    agent A(*, *)
+    agent B(*)
+    agent Lone(*)
+    agent Unused(*)
+    rule A(_, x) B(_) { B(x) }
+    rule A(_, x) A(_, y) { B(x) B(y) }
+    net n(r) { A(p, r) B(p) Lone(s) Lone(s) }
+    net m(r) { A(r, t) B(r) }
+  ";
+  assert_eq!(
+    findings(src),
+    [
+      "agent Lone: has no rules [agent_without_rules]",
+      "agent Unused: is never used [unused_agent]",
+      "rule A/A: its agents never meet in any net [rule_never_fires]",
+      "net n: its nodes fall into 2 disconnected parts [disconnected_net]",
+      "net m: variable `t` is used only once [single_use_var]",
+    ]
+  );
+}
+
+#[test]
+fn allows_lints() {
+  let src = "
+    agent A(*, *)
+    agent B(*)
+    #[allow(agent_without_rules)]
+    agent Lone(*)
+    #[allow(unused_agent)]
+    agent Unused(*)
+    rule A(_, x) B(_) { B(x) }
+    #[allow(rule_never_fires)]
+    rule A(_, x) A(_, y) { B(x) B(y) }
+    #[allow(disconnected_net)]
+    net n(r) { A(p, r) B(p) Lone(s) Lone(s) }
+    #[allow(single_use_var)]
+    net m(r) { A(r, t) B(r) }
+  ";
+  assert!(findings(src).is_empty());
+  // Allowing one lint leaves the others.
+  let src = "#[allow(single_use_var)] net n(r) { B(r) } #[allow(unused_agent)] agent B(*)";
+  assert_eq!(findings(src), ["agent B: has no rules [agent_without_rules]"]);
+}
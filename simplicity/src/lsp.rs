@@ -0,0 +1,610 @@
+use std::{
+  collections::{HashMap, HashSet},
+  io::{self, BufRead, Write},
+  ops::Range,
+};
+
+use logos::Logos;
+
+use crate::{
+  ast::System,
+  check::{self, Item},
+  json::Json,
+  lexer::Token,
+  parser::{ParseError, SimplicityParser},
+};
+
+/// The largest message accepted from the client, in bytes.
+pub const MAX_MESSAGE: usize = 64 << 20;
+
+/// Serves the Language Server Protocol over stdin and stdout.
+pub fn run() -> io::Result<()> {
+  let mut server = Server { documents: HashMap::new(), shut_down: false };
+  let mut stdin = io::stdin().lock();
+  let mut stdout = io::stdout().lock();
+  while let Some(body) = read_message(&mut stdin)? {
+    for reply in server.receive(&body) {
+      let reply = reply.to_string();
+      write!(stdout, "Content-Length: {}\r\n\r\n{reply}", reply.len())?;
+      stdout.flush()?;
+    }
+  }
+  Ok(())
+}
+
+/// Reads the body of the next message, or `None` at the end of the input.
+fn read_message(input: &mut impl BufRead) -> io::Result<Option<String>> {
+  let mut length = None;
+  loop {
+    let mut line = String::new();
+    if input.read_line(&mut line)? == 0 {
+      return Ok(None);
+    }
+    let line = line.trim_end();
+    if line.is_empty() {
+      break;
+    }
+    if let Some(value) = line.strip_prefix("Content-Length:") {
+      length = value.trim().parse().ok();
+    }
+  }
+  let length = length.unwrap_or(0);
+  if length > MAX_MESSAGE {
+    return Err(io::Error::new(
+      io::ErrorKind::InvalidData,
+      format!("a message of {length} bytes is over the limit of {MAX_MESSAGE}"),
+    ));
+  }
+  let mut body = vec![0; length];
+  input.read_exact(&mut body)?;
+  Ok(Some(String::from_utf8_lossy(&body).into_owned()))
+}
+
+struct Server {
+  documents: HashMap<String, Document>,
+  /// Whether a `shutdown` request has arrived, after which `exit` is expected.
+  shut_down: bool,
+}
+
+impl Server {
+  /// Handles the message `body`, answering a body that is not JSON with a
+  /// parse error.
+  fn receive(&mut self, body: &str) -> Vec<Json> {
+    match Json::parse(body) {
+      Some(message) => self.handle(&message),
+      None => vec![error_reply(Json::Null, -32700, "the message is not valid JSON".into())],
+    }
+  }
+
+  fn handle(&mut self, message: &Json) -> Vec<Json> {
+    let method = message.get("method").and_then(Json::as_str).unwrap_or("");
+    let params = message.get("params").unwrap_or(&Json::Null);
+    let Some(id) = message.get("id").cloned() else {
+      return self.notify(method, params).into_iter().collect();
+    };
+    let result = match method {
+      "initialize" => Some(capabilities()),
+      "shutdown" => {
+        self.shut_down = true;
+        Some(Json::Null)
+      }
+      "textDocument/definition" => self.at(params).map(|(doc, uri, offset)| {
+        doc.definition(offset).map(|span| doc.location(uri, span)).unwrap_or(Json::Null)
+      }),
+      "textDocument/references" => self.at(params).map(|(doc, uri, offset)| {
+        let declaration = params
+          .get("context")
+          .and_then(|x| x.get("includeDeclaration"))
+          .and_then(Json::as_bool)
+          .unwrap_or(true);
+        let spans = doc.references(offset, declaration);
+        Json::Array(spans.into_iter().map(|span| doc.location(uri, span)).collect())
+      }),
+      "textDocument/hover" => self.at(params).map(|(doc, _, offset)| doc.hover(offset)),
+      "textDocument/rename" => self.at(params).map(|(doc, uri, offset)| {
+        let name = params.get("newName").and_then(Json::as_str).unwrap_or("");
+        let edits = doc
+          .references(offset, true)
+          .into_iter()
+          .map(|span| Json::object([("range", doc.range(span)), ("newText", name.into())]))
+          .collect();
+        Json::object([("changes", Json::Object(vec![(uri.to_owned(), Json::Array(edits))]))])
+      }),
+      "textDocument/codeLens" => self.document(params).map(|(doc, _)| doc.code_lenses()),
+      _ => None,
+    };
+    let reply = match result {
+      Some(result) => Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", result)]),
+      None if method.starts_with("textDocument/") => {
+        Json::object([("jsonrpc", "2.0".into()), ("id", id), ("result", Json::Null)])
+      }
+      None => error_reply(id, -32601, format!("unknown method `{method}`")),
+    };
+    vec![reply]
+  }
+
+  fn notify(&mut self, method: &str, params: &Json) -> Option<Json> {
+    if method == "exit" {
+      // Exiting without being shut down first is an error.
+      std::process::exit(if self.shut_down { 0 } else { 1 });
+    }
+    let document = params.get("textDocument")?;
+    let uri = document.get("uri")?.as_str()?;
+    let text = match method {
+      "textDocument/didOpen" => document.get("text")?.as_str()?,
+      "textDocument/didChange" => {
+        params.get("contentChanges")?.as_array()?.last()?.get("text")?.as_str()?
+      }
+      "textDocument/didClose" => {
+        self.documents.remove(uri);
+        return None;
+      }
+      _ => return None,
+    };
+    let doc = Document::new(text.to_owned());
+    let diagnostics = doc.diagnostics.iter().map(|(span, message)| {
+      Json::object([
+        ("range", doc.range(span.clone())),
+        ("severity", 1u32.into()),
+        ("source", "simplicity".into()),
+        ("message", message.clone().into()),
+      ])
+    });
+    let notification = Json::object([
+      ("jsonrpc", "2.0".into()),
+      ("method", "textDocument/publishDiagnostics".into()),
+      (
+        "params",
+        Json::object([("uri", uri.into()), ("diagnostics", Json::Array(diagnostics.collect()))]),
+      ),
+    ]);
+    self.documents.insert(uri.to_owned(), doc);
+    Some(notification)
+  }
+
+  fn document<'a>(&'a self, params: &'a Json) -> Option<(&'a Document, &'a str)> {
+    let uri = params.get("textDocument")?.get("uri")?.as_str()?;
+    Some((self.documents.get(uri)?, uri))
+  }
+
+  fn at<'a>(&'a self, params: &'a Json) -> Option<(&'a Document, &'a str, usize)> {
+    let (doc, uri) = self.document(params)?;
+    let position = params.get("position")?;
+    let line = position.get("line")?.as_u32()?;
+    let character = position.get("character")?.as_u32()?;
+    Some((doc, uri, doc.offset(line, character)))
+  }
+}
+
+fn error_reply(id: Json, code: i32, message: String) -> Json {
+  Json::object([
+    ("jsonrpc", "2.0".into()),
+    ("id", id),
+    ("error", Json::object([("code", Json::Number(code.into())), ("message", message.into())])),
+  ])
+}
+
+fn capabilities() -> Json {
+  Json::object([(
+    "capabilities",
+    Json::object([
+      ("textDocumentSync", 1u32.into()),
+      ("definitionProvider", true.into()),
+      ("referencesProvider", true.into()),
+      ("hoverProvider", true.into()),
+      ("renameProvider", true.into()),
+      ("codeLensProvider", Json::object([("resolveProvider", false.into())])),
+    ]),
+  )])
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+enum Symbol {
+  Agent(String),
+  /// A variable, scoped to the `n`th rule or net in the file.
+  Var(usize, String),
+}
+
+struct Document {
+  text: String,
+  line_starts: Vec<usize>,
+  system: Option<System>,
+  diagnostics: Vec<(Range<usize>, String)>,
+  occurrences: Vec<(Range<usize>, Symbol)>,
+  definitions: HashMap<Symbol, Range<usize>>,
+  rules: Vec<Range<usize>>,
+  nets: Vec<Range<usize>>,
+}
+
+impl Document {
+  fn new(text: String) -> Self {
+    let line_starts = [0].into_iter().chain(text.match_indices('\n').map(|(i, _)| i + 1)).collect();
+    let mut doc = Document {
+      text,
+      line_starts,
+      system: None,
+      diagnostics: Vec::new(),
+      occurrences: Vec::new(),
+      definitions: HashMap::new(),
+      rules: Vec::new(),
+      nets: Vec::new(),
+    };
+    doc.index();
+    doc.analyze();
+    doc
+  }
+
+  /// Finds every agent and variable in the file by scanning its tokens, so
+  /// that navigation keeps working while the file does not parse.
+  fn index(&mut self) {
+    let mut tokens = Vec::new();
+    for (token, span) in Token::lexer(&self.text).spanned() {
+      match token {
+        Ok(token) => tokens.push((token, span)),
+        Err(()) => {
+          self.diagnostics.push((span, "unrecognized token".into()));
+          return;
+        }
+      }
+    }
+
+    // The agents without auxiliary ports, whose bare names are nested nodes.
+    let nullary = tokens
+      .windows(5)
+      .filter(|x| {
+        let kinds = x.iter().map(|x| x.0).collect::<Vec<_>>();
+        kinds == [Token::Agent, Token::Ident, Token::OpenParen, Token::Star, Token::CloseParen]
+      })
+      .map(|x| self.text[x[1].1.clone()].to_owned())
+      .collect::<HashSet<_>>();
+
+    let mut item = None;
+    let mut in_agent = false;
+    let mut in_attribute = false;
+    let mut in_body = false;
+    let mut depth = 0;
+    for (i, (token, span)) in tokens.iter().enumerate() {
+      let previous = i.checked_sub(1).map(|i| tokens[i].0);
+      match token {
+        Token::Hash => in_attribute = true,
+        Token::CloseBracket => in_attribute = false,
+        Token::OpenParen => depth += 1,
+        Token::CloseParen => depth -= 1,
+        Token::OpenBrace if depth == 0 && !in_agent => in_body = true,
+        Token::Agent => in_agent = true,
+        Token::Rule => {
+          in_agent = false;
+          in_body = false;
+          item = Some(self.rules.len() + self.nets.len());
+          self.rules.push(span.clone());
+        }
+        Token::Net => {
+          in_agent = false;
+          in_body = false;
+          item = Some(self.rules.len() + self.nets.len());
+          self.nets.push(span.clone());
+        }
+        Token::Ident => {
+          let name = self.text[span.clone()].to_owned();
+          let symbol = if previous == Some(Token::Agent) {
+            Symbol::Agent(name)
+          } else if in_agent
+            || in_attribute
+            || previous == Some(Token::Net)
+            || previous == Some(Token::Colon) && depth == 0
+            || tokens.get(i + 1).is_some_and(|x| x.0 == Token::Colon)
+          {
+            continue;
+          } else if tokens.get(i + 1).is_some_and(|x| x.0 == Token::OpenParen) {
+            self.occurrences.push((span.clone(), Symbol::Agent(name)));
+            continue;
+          } else if in_body && nullary.contains(&name) {
+            // A nested node without auxiliary ports.
+            self.occurrences.push((span.clone(), Symbol::Agent(name)));
+            continue;
+          } else if in_body && depth > 1 && name == "_" {
+            // The principal port of a nested node.
+            continue;
+          } else if let Some(item) = item {
+            Symbol::Var(item, name)
+          } else {
+            continue;
+          };
+          self.definitions.entry(symbol.clone()).or_insert(span.clone());
+          self.occurrences.push((span.clone(), symbol));
+        }
+        _ => {}
+      }
+    }
+  }
+
+  fn analyze(&mut self) {
+    if !self.diagnostics.is_empty() {
+      return;
+    }
+    let system = match SimplicityParser::parse(&self.text) {
+      Ok(system) => system,
+      Err(error) => {
+        let (found, message) = match error {
+          ParseError::LexError => ("", "unrecognized token".to_owned()),
+          ParseError::UnexpectedToken { expected, found } => {
+            (found, format!("expected one of {expected:?}, found `{found}`"))
+          }
+          ParseError::DuplicateAgentDef { name } => {
+            (name, format!("duplicate definition of agent `{name}`"))
+          }
+          ParseError::UndefinedAgents { names } => {
+            for (name, similar) in names {
+              let message = match similar {
+                Some(similar) => format!("undefined agent `{name}`; did you mean `{similar}`?"),
+                None => format!("undefined agent `{name}`"),
+              };
+              let span = self.span_of(name);
+              self.diagnostics.push((span, message));
+            }
+            return;
+          }
+          ParseError::UnknownAttribute { name } => (name, format!("unknown attribute `{name}`")),
+          ParseError::UnknownLint { name } => (name, format!("unknown lint `{name}`")),
+          ParseError::MisplacedAttribute { name } => {
+            (name, format!("attribute `{name}` cannot be applied to an agent"))
+          }
+          ParseError::MisplacedLint { name, item } => {
+            (name, format!("lint `{name}` does not apply to {item}s"))
+          }
+          ParseError::UnknownVerdict { name } => {
+            (name, format!("unknown verdict `{name}`; expected `simple` or `non_simple`"))
+          }
+          ParseError::DuplicateExpectation { name } => {
+            (name, "the expected verdict is declared twice".to_owned())
+          }
+          ParseError::NestedPrincipal { name } => {
+            (name, format!("the principal port of a nested node must be `_`, found `{name}`"))
+          }
+          ParseError::DuplicatePort { name } => (name, format!("port `{name}` is given twice")),
+          ParseError::UnknownPort { agent, name } => {
+            (name, format!("agent `{agent}` has no port `{name}`"))
+          }
+          ParseError::PositionalAfterNamed { name } => {
+            (name, format!("ports given in order must come before port `{name}`"))
+          }
+          ParseError::MissingPort { agent, port } => {
+            (agent, format!("{port} of agent `{agent}` is not given"))
+          }
+          ParseError::InvalidArrow { arrow } => (arrow, format!("invalid relation `{arrow}`")),
+          ParseError::ReflexiveAxiom { port } => {
+            (port, format!("port `{port}` cannot be related to itself"))
+          }
+          ParseError::ReflexiveConstraint { name } => {
+            (name, format!("variable `{name}` cannot be related to itself"))
+          }
+          ParseError::ContradictoryAxioms { agent } => {
+            (agent, format!("the axioms of agent `{agent}` contradict its partition"))
+          }
+          ParseError::UnconnectedVar { name } => {
+            (name, format!("variable `{name}` is not connected to any port"))
+          }
+        };
+        self.diagnostics.push((self.span_of(found), message));
+        return;
+      }
+    };
+    let rules =
+      system.rules.iter().flat_map(|rule| [&rule.a, &rule.b].into_iter().chain(&rule.result));
+    let nodes = rules.chain(system.nets.iter().flat_map(|net| &net.nodes));
+    for node in nodes {
+      if let (Some(span), Some(error)) = (node.span, check::arity_error(&system, node)) {
+        self.diagnostics.push((span.start..span.end, error));
+      }
+    }
+    self.system = Some(system);
+  }
+
+  /// The span of `slice` within the text, where a parse error borrowed it
+  /// from. A slice from elsewhere, like the empty one standing for no token,
+  /// is placed at the end of the text.
+  fn span_of(&self, slice: &str) -> Range<usize> {
+    let start = (slice.as_ptr() as usize)
+      .checked_sub(self.text.as_ptr() as usize)
+      .filter(|start| start + slice.len() <= self.text.len())
+      .unwrap_or(self.text.len());
+    start..start + slice.len().min(self.text.len() - start)
+  }
+
+  fn symbol_at(&self, offset: usize) -> Option<&Symbol> {
+    self
+      .occurrences
+      .iter()
+      .find(|(span, _)| span.start <= offset && offset <= span.end)
+      .map(|(_, symbol)| symbol)
+  }
+
+  fn definition(&self, offset: usize) -> Option<Range<usize>> {
+    self.definitions.get(self.symbol_at(offset)?).cloned()
+  }
+
+  fn references(&self, offset: usize, declaration: bool) -> Vec<Range<usize>> {
+    let Some(symbol) = self.symbol_at(offset) else { return Vec::new() };
+    let definition = self.definitions.get(symbol);
+    self
+      .occurrences
+      .iter()
+      .filter(|(span, s)| s == symbol && (declaration || Some(span) != definition))
+      .map(|(span, _)| span.clone())
+      .collect()
+  }
+
+  fn hover(&self, offset: usize) -> Json {
+    let (Some(Symbol::Agent(name)), Some(system)) = (self.symbol_at(offset), &self.system) else {
+      return Json::Null;
+    };
+    let Some(agent) = system.agents.defs.values().find(|x| &x.name == name) else {
+      return Json::Null;
+    };
+    let value = format!("```\nagent {}\n```", agent.value.show(name));
+    Json::object([(
+      "contents",
+      Json::object([("kind", "markdown".into()), ("value", value.into())]),
+    )])
+  }
+
+  fn code_lenses(&self) -> Json {
+    let Some(system) = self.system.as_ref().filter(|_| self.diagnostics.is_empty()) else {
+      return Json::Array(Vec::new());
+    };
+    let rules = self.rules.iter().enumerate().map(|(i, span)| (span, Item::Rule(i)));
+    let nets = self.nets.iter().enumerate().map(|(i, span)| (span, Item::Net(i)));
+    let lenses = rules.chain(nets).map(|(span, item)| {
+      let verdict = item.check(system);
+      let title = match item.expect(system) {
+        Some(expect) if expect != verdict => format!("{verdict}, expected {expect}"),
+        _ => verdict.to_string(),
+      };
+      Json::object([
+        ("range", self.range(span.clone())),
+        ("command", Json::object([("title", title.into()), ("command", "".into())])),
+      ])
+    });
+    Json::Array(lenses.collect())
+  }
+
+  fn location(&self, uri: &str, span: Range<usize>) -> Json {
+    Json::object([("uri", uri.into()), ("range", self.range(span))])
+  }
+
+  fn range(&self, span: Range<usize>) -> Json {
+    Json::object([("start", self.position(span.start)), ("end", self.position(span.end))])
+  }
+
+  fn position(&self, offset: usize) -> Json {
+    let line = self.line_starts.partition_point(|&x| x <= offset) - 1;
+    let character = self.text[self.line_starts[line]..offset].encode_utf16().count();
+    Json::object([("line", line.into()), ("character", character.into())])
+  }
+
+  fn offset(&self, line: u32, character: u32) -> usize {
+    let Some(&start) = self.line_starts.get(line as usize) else { return self.text.len() };
+    let mut units = 0;
+    for (i, c) in self.text[start..].char_indices() {
+      if units >= character as usize || c == '\n' {
+        return start + i;
+      }
+      units += c.len_utf16();
+    }
+    self.text.len()
+  }
+}
+
+#[cfg(test)]
+fn diagnostics(text: &str) -> Json {
+  let mut server = Server { documents: HashMap::new(), shut_down: false };
+  let params = Json::object([(
+    "textDocument",
+    Json::object([("uri", "file:///a.simp".into()), ("text", text.into())]),
+  )]);
+  let notification = server.notify("textDocument/didOpen", &params).unwrap();
+  notification.get("params").unwrap().get("diagnostics").unwrap().clone()
+}
+
+#[cfg(test)]
+fn ranges(text: &str) -> Vec<(u32, u32, u32, u32, String)> {
+  let position = |x: &Json, key| {
+    let x = x.get(key).unwrap();
+    (x.get("line").unwrap().as_u32().unwrap(), x.get("character").unwrap().as_u32().unwrap())
+  };
+  let diagnostics = diagnostics(text);
+  let diagnostics = diagnostics.as_array().unwrap();
+  diagnostics
+    .iter()
+    .map(|x| {
+      let range = x.get("range").unwrap();
+      let (start, end) = (position(range, "start"), position(range, "end"));
+      (start.0, start.1, end.0, end.1, x.get("message").unwrap().as_str().unwrap().to_owned())
+    })
+    .collect()
+}
+
+#[test]
+fn diagnoses_parse_errors_at_the_token() {
+  let text = "agent A(*)\nnet n(x) {\n  A(x y)\n}\n";
+  let [(line, start, end_line, end, message)] = &ranges(text)[..] else { panic!() };
+  assert_eq!((*line, *start, *end_line, *end), (2, 6, 2, 7));
+  assert!(message.ends_with("found `y`"), "{message}");
+}
+
+#[test]
+fn diagnoses_each_undefined_agent_once() {
+  let text = "agent Con(*, *)\nnet n(x) {\n  Cn(x, y)\n  Cn(y, Dup(_))\n}\n";
+  let diagnostics = ranges(text);
+  assert_eq!(
+    diagnostics,
+    [
+      (2, 2, 2, 4, "undefined agent `Cn`; did you mean `Con`?".into()),
+      (3, 8, 3, 11, "undefined agent `Dup`".into()),
+    ]
+  );
+}
+
+#[test]
+fn counts_characters_in_utf16() {
+  let text = "agent A(*)\n// é🦀\nnet n(x) { A(x, y) }\n";
+  let [(line, start, _, end, message)] = &ranges(text)[..] else { panic!() };
+  assert_eq!((*line, *start, *end), (2, 11, 12));
+  assert_eq!(message, "agent `A(*)` has 1 ports, but 2 were given");
+  let text = "agent A(*) /* é🦀 */ agent A(*)\n";
+  let [(line, start, _, end, _)] = &ranges(text)[..] else { panic!() };
+  assert_eq!((*line, *start, *end), (0, 27, 28));
+  let doc = Document::new("// é🦀\nx".to_owned());
+  assert_eq!(doc.offset(0, 6), "// é🦀".len());
+  assert_eq!(doc.position("// é🦀".len()).get("character").unwrap().as_u32(), Some(6));
+}
+
+#[test]
+fn places_errors_without_a_token_at_the_end() {
+  let doc = Document::new("agent A(*)".to_owned());
+  assert_eq!(doc.span_of(""), 10..10);
+  assert_eq!(doc.span_of(&doc.text[6..7]), 6..7);
+  let text = "net n(x) {";
+  let [(line, start, _, end, _)] = &ranges(text)[..] else { panic!() };
+  assert_eq!((*line, *start, *end), (0, 10, 10));
+}
+
+#[test]
+fn diagnoses_arity_at_nested_nodes() {
+  let text = "agent A(*, *)\nnet n(x) { A(x, A(_, y, z)) }\n";
+  assert_eq!(
+    ranges(text),
+    [(1, 16, 1, 17, "agent `A(*, *)` has 2 ports, but 3 were given".into())]
+  );
+}
+
+#[test]
+fn reads_messages_up_to_the_limit() {
+  let mut input = io::Cursor::new("Content-Length: 2\r\n\r\n{}Content-Length: 3\r\n\r\n");
+  assert_eq!(read_message(&mut input).unwrap().as_deref(), Some("{}"));
+  assert!(read_message(&mut input).is_err());
+  let mut input = io::Cursor::new(format!("Content-Length: {}\r\n\r\n", MAX_MESSAGE + 1));
+  assert_eq!(read_message(&mut input).unwrap_err().kind(), io::ErrorKind::InvalidData);
+  assert!(read_message(&mut io::Cursor::new("")).unwrap().is_none());
+}
+
+#[test]
+fn answers_malformed_messages_with_parse_errors() {
+  let mut server = Server { documents: HashMap::new(), shut_down: false };
+  let [reply] = &server.receive("{\"id\": 1,")[..] else { panic!() };
+  assert_eq!(reply.get("id"), Some(&Json::Null));
+  let code = reply.get("error").and_then(|x| x.get("code"));
+  assert_eq!(code, Some(&Json::Number(-32700.0)));
+
+  let [reply] = &server.receive("{\"id\": 1, \"method\": \"shutdown\"}")[..] else { panic!() };
+  assert_eq!(reply.get("result"), Some(&Json::Null));
+  assert!(server.shut_down);
+}
+
+#[test]
+fn tells_vars_from_agents_of_the_same_name() {
+  let doc = Document::new("agent A(*, *)\nnet n(x) { A(x, A) A(A, x) }\n".to_owned());
+  assert!(doc.diagnostics.is_empty(), "{:?}", doc.diagnostics);
+  assert_eq!(doc.definition(35), Some(30..31));
+  assert_eq!(doc.definition(25), Some(6..7));
+}
@@ -1,61 +1,435 @@
 pub mod arrow;
 pub mod ast;
+pub mod certificate;
+pub mod check;
 pub mod diagram;
+pub mod diff;
+pub mod exact;
+pub mod export;
+pub mod infer;
+pub mod interface;
+pub mod ivy;
+pub mod json;
 pub mod lexer;
+pub mod lint;
+pub mod lsp;
 pub mod parser;
+pub mod project;
+pub mod random;
+pub mod refine;
+pub mod repl;
 pub mod scope;
+pub mod suggest;
+pub mod toml;
+pub mod watch;
 
-use std::{env::args, fs::read_to_string};
+use std::{
+  collections::HashMap,
+  env::args,
+  fs::{create_dir_all, read_to_string, write},
+  path::Path,
+  process::exit,
+};
 
-use diagram::Diagram;
+use ast::{System, Verdict};
+use check::Item;
+use infer::Mode;
+use ivy::IvyParser;
 use parser::SimplicityParser;
+use refine::Contradiction;
+use repl::Repl;
+
+const USAGE: &str = "\
+usage: simplicity <path>
+       simplicity [suggest | infer [--finest] | interface | lint | refines] <path>
+       simplicity exact [--scenario] <path>
+       simplicity [smt | dimacs | solved] <path> <dir>
+       simplicity ivy [--partition <kind>=<ports>]... <path>
+       simplicity certify <path> <out>
+       simplicity verify <path> <certificate>
+       simplicity baseline <path> <out>
+       simplicity diff <old> <new>
+       simplicity project <root>
+       simplicity --watch <path>...
+       simplicity repl [<path>]
+       simplicity random <seed>
+       simplicity lsp
+";
 
 fn main() {
-  let path = args().nth(1).expect("must supply path");
+  let args = args().skip(1).collect::<Vec<_>>();
+  match &args.iter().map(|x| &**x).collect::<Vec<_>>()[..] {
+    ["suggest", path] => suggest(&load(path)),
+    ["infer", path] => infer(&load(path), Mode::Coarsest),
+    ["infer", "--finest", path] => infer(&load(path), Mode::Finest),
+    ["interface", path] => interface(&load(path)),
+    ["lint", path] => lint(&load(path)),
+    ["refines", path] => refines(&load(path)),
+    ["random", seed] => {
+      print!("{}", random::system(&mut util::rng::Rng::new(seed.parse().unwrap())))
+    }
+    ["exact", path] => exact(&load(path), false),
+    ["exact", "--scenario", path] => exact(&load(path), true),
+    ["smt", path, dir] => export(&load(path), dir, "smt2"),
+    ["dimacs", path, dir] => export(&load(path), dir, "cnf"),
+    ["solved", path, dir] => solved(&load(path), dir),
+    ["certify", path, out] => certify(&load(path), out),
+    ["verify", path, certificate] => verify(&load(path), certificate),
+    ["lsp"] => lsp::run().unwrap_or_else(|error| {
+      eprintln!("error: {error}");
+      exit(1)
+    }),
+    ["repl"] => Repl::new(None).unwrap().run().unwrap(),
+    ["repl", path] => {
+      let src = read_to_string(path).unwrap();
+      Repl::new(Some(&src)).unwrap().run().unwrap()
+    }
+    ["project", root] => project(root),
+    ["diff", old, new] => diff(old, new),
+    ["baseline", path, out] => {
+      write(out, diff::write_baseline(&diff::verdicts(&load(path)))).unwrap()
+    }
+    ["--watch", paths @ ..] if !paths.is_empty() => watch::watch(paths),
+    ["ivy", args @ ..] if !args.is_empty() => ivy(args),
+    [path] => check(&load(path)),
+    _ => {
+      eprint!("{USAGE}");
+      exit(2);
+    }
+  }
+}
+
+fn load(path: &str) -> System {
+  let src = read_to_string(path).unwrap();
+  if path.ends_with(".iv") {
+    return IvyParser::parse(&src, &HashMap::new()).unwrap();
+  }
+  SimplicityParser::parse(&src).unwrap()
+}
+
+fn ivy(args: &[&str]) {
+  let (path, flags) = args.split_last().unwrap();
+  let mut partitions = HashMap::new();
+  let mut flags = flags.iter();
+  while let Some(&flag) = flags.next() {
+    let spec = match flag {
+      "--partition" => flags.next().copied(),
+      _ => flag.strip_prefix("--partition="),
+    };
+    let Some((kind, ports)) = spec.and_then(|x| x.split_once('=')) else {
+      panic!("expected `--partition <kind>=<ports>`, found `{flag}`")
+    };
+    let def = ivy::parse_partition(ports).unwrap_or_else(|| panic!("invalid ports `{ports}`"));
+    partitions.insert(kind.to_owned(), def);
+  }
   let src = read_to_string(path).unwrap();
-  let system = SimplicityParser::parse(&src).unwrap();
+  check(&IvyParser::parse(&src, &partitions).unwrap());
+}
+
+/// Prints the verdict of every item, except that items declaring the verdict
+/// they expect are only reported when it differs, and exits with an error if
+/// any of them does or any assertion fails.
+fn check(system: &System) {
+  let (mut expected, mut unexpected, mut failed) = (0, 0, false);
+  for item in Item::all(system) {
+    let mut diagram = item.diagram(system);
+    let verdict = Verdict::of(&mut diagram);
+    for failure in item.failed_assertions(system, &diagram) {
+      println!("{}: {failure}", item.name(system));
+      failed = true;
+    }
+    match item.expect(system) {
+      None => println!("{}: {verdict}", item.name(system)),
+      Some(expect) if expect == verdict => expected += 1,
+      Some(expect) => {
+        println!("{}: {verdict}, expected {expect}", item.name(system));
+        unexpected += 1;
+      }
+    }
+  }
+  if expected + unexpected != 0 {
+    println!("{expected} of {} expectations met", expected + unexpected);
+  }
+  if unexpected != 0 || failed {
+    exit(1);
+  }
+}
+
+/// Prints every finding of the lints, and exits with an error if there are
+/// any.
+fn lint(system: &System) {
+  let findings = lint::lint(system);
+  for finding in &findings {
+    println!("{finding}");
+  }
+  if !findings.is_empty() {
+    exit(1);
+  }
+}
+
+/// Lists the items whose verdict changed between two files, either of which
+/// may be a baseline saved by `baseline` rather than a system.
+fn diff(old: &str, new: &str) {
+  let verdicts = |path: &str| {
+    if path.ends_with(".nets") || path.ends_with(".iv") {
+      diff::verdicts(&load(path))
+    } else {
+      diff::read_baseline(&read_to_string(path).unwrap()).unwrap_or_else(|e| panic!("{path}: {e}"))
+    }
+  };
+  let changes = diff::diff(&verdicts(old), &verdicts(new));
+  if changes.is_empty() {
+    println!("no verdict changed");
+  }
+  for (name, change) in changes {
+    println!("{name}: {change}");
+  }
+}
+
+fn suggest(system: &System) {
+  for i in 0..system.rules.len() {
+    let item = Item::Rule(i);
+    if item.check(system) == Verdict::Simple {
+      continue;
+    }
+    println!("{}: non-simple", item.name(system));
+    let suggestions = suggest::suggest(system, i);
+    if suggestions.is_empty() {
+      println!("  no partition change makes this rule simple");
+    }
+    for changes in suggestions {
+      let changes = changes
+        .iter()
+        .map(|(agent, def)| format!("agent {}", def.show(&system.agents.defs[*agent].name)))
+        .collect::<Vec<_>>();
+      println!("  {}", changes.join("; "));
+    }
+  }
+}
 
-  for rule in &system.rules {
-    let mut diagram = Diagram::default();
+fn infer(system: &System, mode: Mode) {
+  let assignments = infer::infer(system, mode);
+  if assignments.is_empty() {
+    println!("no partition assignment makes every rule and net simple");
+  }
+  for (i, agents) in assignments.iter().enumerate() {
+    println!("assignment {}:", i + 1);
+    for (agent, def) in agents.iter() {
+      println!("  agent {}", def.show(&system.agents.defs[agent].name));
+    }
+  }
+}
+
+fn interface(system: &System) {
+  for item in Item::all(system) {
+    let mut diagram = item.diagram(system);
+    println!("{}: {}", item.name(system), Verdict::of(&mut diagram));
+
+    let vars = item.vars(system);
+    let names = item.ports(system).into_iter().map(|v| &*vars.defs[v].name).collect::<Vec<_>>();
+    let width = names.iter().map(|x| x.len()).max().unwrap_or(0);
+    let relation = interface::relation(&diagram);
+    if names.is_empty() {
+      continue;
+    }
+
+    print!("  {:width$}", "");
+    for name in &names {
+      print!(" {name:>5}");
+    }
+    println!();
+    for (i, row) in relation.iter().enumerate() {
+      print!("  {:width$}", names[i]);
+      for (j, arrow) in row.iter().enumerate() {
+        let column = names[j].len().max(5);
+        if i == j {
+          print!(" {:column$}", "");
+        } else {
+          print!(" {:>column$}", format!("{arrow:?}"));
+        }
+      }
+      println!();
+    }
 
-    let a = &system.agents.defs[rule.a.agent];
-    let b = &system.agents.defs[rule.b.agent];
-    diagram.insert_free_ports(
-      rule.a.ports[1..].iter().copied(),
-      a.value.auxiliary.iter().map(|x| x.len()),
-    );
-    diagram.insert_free_ports(
-      rule.b.ports[1..].iter().copied(),
-      b.value.auxiliary.iter().map(|x| x.len()),
-    );
+    match interface::partition(&relation) {
+      Some(groups) => {
+        let groups = groups
+          .iter()
+          .map(|group| {
+            let names = group.iter().map(|&i| names[i]).collect::<Vec<_>>().join(", ");
+            if group.len() == 1 {
+              names
+            } else {
+              format!("{{{names}}}")
+            }
+          })
+          .collect::<Vec<_>>();
+        println!("  partition: ({})", groups.join(", "));
+      }
+      None => println!("  partition: none"),
+    }
+  }
+}
 
-    for node in &rule.result {
-      let agent = &system.agents.defs[node.agent].value;
-      diagram.insert_agent(node.ports.iter().copied(), agent.auxiliary.iter().map(|x| x.len()));
+fn refines(system: &System) {
+  for (i, rule) in system.rules.iter().enumerate() {
+    let item = Item::Rule(i);
+    let strengthenings = match refine::strengthenings(system, rule) {
+      Ok(strengthenings) => strengthenings,
+      Err(Contradiction::ActivePair) => {
+        println!("{}: the active pair is contradictory", item.name(system));
+        continue;
+      }
+      Err(Contradiction::Result) => {
+        println!("{}: non-simple", item.name(system));
+        continue;
+      }
+    };
+    if strengthenings.is_empty() {
+      println!("{}: refines", item.name(system));
+      continue;
+    }
+    println!("{}: strengthens", item.name(system));
+    let vars = item.vars(system);
+    let ports = item.ports(system);
+    for strengthening in strengthenings {
+      let a = &vars.defs[ports[strengthening.a]].name;
+      let b = &vars.defs[ports[strengthening.b]].name;
+      println!("  {a} {b}: {:?} -> {:?}", strengthening.lhs, strengthening.rhs);
     }
+  }
+}
 
-    diagram.complete();
-    assert!(diagram.is_complete());
+fn exact(system: &System, show_scenario: bool) {
+  let mut disagreements = Vec::new();
+  for item in Item::all(system) {
+    let name = item.name(system);
+    let diagram = item.diagram(system);
+    let verdict = Verdict::of(&mut diagram.clone());
+    let scenario = match exact::scenario(&diagram) {
+      Ok(scenario) => scenario,
+      Err(error) => {
+        println!("{name}: {error}");
+        continue;
+      }
+    };
+    let exact = if scenario.is_some() { Verdict::Simple } else { Verdict::NonSimple };
+    println!("{name}: {exact}");
+    if exact != verdict {
+      disagreements.push(format!("{name}: the fast check says {verdict}"));
+    }
+    if let (true, Some(scenario)) = (show_scenario, scenario) {
+      for a in scenario.nodes.keys() {
+        println!("  {a:?}: {}", item.describe_node(system, &scenario, a));
+      }
+      for (a, node) in scenario.graph.nodes.iter() {
+        let mut edges = node.edges.iter().filter(|(&b, _)| a < b).collect::<Vec<_>>();
+        edges.sort_by_key(|(&b, _)| b);
+        for (b, arrow) in edges {
+          println!("  {a:?} {b:?} {arrow:?}");
+        }
+      }
+    }
+  }
+  if !disagreements.is_empty() {
+    println!("disagreements with the fast check:");
+    for disagreement in disagreements {
+      println!("  {disagreement}");
+    }
+  }
+}
 
-    let simple = !diagram.is_contradictory();
-    println!("rule {}/{}: {}", a.name, b.name, if simple { "simple" } else { "non-simple" });
+fn export(system: &System, dir: &str, extension: &str) {
+  create_dir_all(dir).unwrap();
+  for item in Item::all(system) {
+    let name = item.name(system);
+    let problem = export::Problem::encode(&item.diagram(system));
+    let out = if extension == "smt2" { problem.smt(&name) } else { problem.dimacs(&name) };
+    write(Path::new(dir).join(format!("{}.{extension}", export::file_name(&name))), out).unwrap();
   }
+}
 
-  for net in &system.nets {
-    let mut diagram = Diagram::default();
+/// Compares the solver output saved as `<item>.out` in `dir` with the verdict
+/// of every item.
+fn solved(system: &System, dir: &str) {
+  for item in Item::all(system) {
+    let name = item.name(system);
+    let path = Path::new(dir).join(format!("{}.out", export::file_name(&name)));
+    let verdict = item.check(system);
+    match read_to_string(path).ok().as_deref().and_then(export::read_result) {
+      None => println!("{name}: no result"),
+      Some(sat) if sat == (verdict == Verdict::Simple) => println!("{name}: agrees ({verdict})"),
+      Some(sat) => {
+        let result = if sat { "sat" } else { "unsat" };
+        println!("{name}: the solver says {result}, but the check says {verdict}");
+      }
+    }
+  }
+}
 
-    diagram
-      .insert_free_ports(net.ports.iter().flatten().copied(), net.ports.iter().map(|x| x.len()));
+fn project(root: &str) {
+  let root = Path::new(root);
+  let config = project::Config::load(root).unwrap_or_else(|error| {
+    println!("error: {error}");
+    exit(1)
+  });
+  let reports = project::files(root, &config)
+    .iter()
+    .map(|path| project::check_file(root, path, &config))
+    .collect::<Vec<_>>();
 
-    for node in &net.nodes {
-      let agent = &system.agents.defs[node.agent].value;
-      diagram.insert_agent(node.ports.iter().copied(), agent.auxiliary.iter().map(|x| x.len()));
+  let mut unexpected = 0;
+  for report in &reports {
+    for line in &report.unexpected {
+      println!("{}: {line}", report.path);
+      unexpected += 1;
     }
+  }
+  if unexpected != 0 {
+    println!();
+  }
 
-    diagram.complete();
-    assert!(diagram.is_complete());
+  let width = reports.iter().map(|x| x.path.len()).chain([5]).max().unwrap();
+  let row = |path: &str, simple, non_simple, expected, unexpected| {
+    println!("{path:width$}  {simple:>6}  {non_simple:>10}  {expected:>8}  {unexpected:>10}");
+  };
+  println!("{:width$}  simple  non-simple  expected  unexpected", "file");
+  for report in &reports {
+    row(&report.path, report.simple, report.non_simple, report.expected, report.unexpected.len());
+  }
+  let total = |f: fn(&project::FileReport) -> usize| reports.iter().map(f).sum::<usize>();
+  row("total", total(|x| x.simple), total(|x| x.non_simple), total(|x| x.expected), unexpected);
+  if unexpected != 0 {
+    exit(1);
+  }
+}
 
-    let simple = !diagram.is_contradictory();
-    println!("net {}: {}", net.name, if simple { "simple" } else { "non-simple" });
+fn certify(system: &System, out: &str) {
+  let (certificates, skipped) = certificate::certify(system);
+  write(out, certificates).unwrap();
+  for name in skipped {
+    println!("{name}: non-simple, not certified");
+  }
+}
+
+fn verify(system: &System, path: &str) {
+  let certificates = certificate::parse(&read_to_string(path).unwrap()).unwrap();
+  let mut ok = true;
+  for (index, item) in Item::all(system).enumerate() {
+    let name = item.name(system);
+    let result = match certificate::find(&certificates, index, &name) {
+      Some(certificate) => certificate::verify(system, item, certificate),
+      None => Err("no certificate".into()),
+    };
+    match result {
+      Ok(()) => println!("{name}: verified"),
+      Err(error) => {
+        println!("{name}: {error}");
+        ok = false;
+      }
+    }
+  }
+  if !ok {
+    exit(1);
   }
 }
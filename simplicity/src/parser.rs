@@ -1,10 +1,17 @@
+use logos::Logos;
+
 use util::{
   lexer::TokenSet,
   parser::{Delimiters, Parser, ParserState},
 };
 
 use crate::{
-  ast::{Agent, AgentDef, NetDef, Node, RuleDef, System, Var},
+  arrow::Arrow,
+  ast::{
+    Agent, AgentDef, Axiom, Constraint, ConstraintKind, Lint, NetDef, Node, Partition, RuleDef,
+    Span, System, Var, Verdict,
+  },
+  diagram::Diagram,
   lexer::Token,
   scope::ScopeBuilder,
 };
@@ -15,19 +22,54 @@ pub struct SimplicityParser<'src> {
   pub vars: ScopeBuilder<'src, Var, ()>,
   pub rules: Vec<RuleDef>,
   pub nets: Vec<NetDef>,
+  /// Set while parsing a file whose agents have already been declared, so
+  /// that their definitions are not taken to be duplicates.
+  declared: bool,
+  /// The names used as agents that are not defined, each where it is first
+  /// used, to be reported together by [`Self::report_undefined`].
+  undefined: Vec<&'src str>,
+  /// The start of the last span taken in the current source and its line, so
+  /// that the line of the next is counted from there rather than from the
+  /// start of the source.
+  last_line: (usize, usize),
 }
 
-#[allow(unused)]
 #[derive(Debug, Clone)]
 pub enum ParseError<'src> {
   LexError,
   UnexpectedToken { expected: TokenSet<Token>, found: &'src str },
   DuplicateAgentDef { name: &'src str },
-  UndefinedAgent { name: &'src str },
+  UndefinedAgents { names: Vec<(&'src str, Option<&'src str>)> },
+  UnknownAttribute { name: &'src str },
+  UnknownLint { name: &'src str },
+  MisplacedAttribute { name: &'src str },
+  MisplacedLint { name: &'src str, item: &'src str },
+  UnknownVerdict { name: &'src str },
+  DuplicateExpectation { name: &'src str },
+  NestedPrincipal { name: &'src str },
+  DuplicatePort { name: &'src str },
+  UnknownPort { agent: &'src str, name: &'src str },
+  PositionalAfterNamed { name: &'src str },
+  MissingPort { agent: &'src str, port: String },
+  InvalidArrow { arrow: &'src str },
+  ReflexiveAxiom { port: &'src str },
+  ReflexiveConstraint { name: &'src str },
+  ContradictoryAxioms { agent: &'src str },
+  UnconnectedVar { name: &'src str },
 }
 
 type Parse<'src, T = ()> = Result<T, ParseError<'src>>;
 
+/// The attributes before a definition.
+#[derive(Default)]
+struct Attributes<'src> {
+  /// The expected verdict, along with the attribute that declared it.
+  expect: Option<(&'src str, Verdict)>,
+  allow: Vec<Lint>,
+  /// The names the allowed lints were written with, in the same order.
+  lints: Vec<&'src str>,
+}
+
 impl<'src> Parser<'src> for SimplicityParser<'src> {
   type Token = Token;
   type Error = ParseError<'src>;
@@ -46,103 +88,520 @@ impl<'src> Parser<'src> for SimplicityParser<'src> {
 }
 
 impl<'src> SimplicityParser<'src> {
-  pub fn parse(src: &'src str) -> Parse<'src, System> {
-    let mut parser = SimplicityParser {
+  pub fn new(src: &'src str) -> Self {
+    SimplicityParser {
       state: ParserState::new(src),
       agents: ScopeBuilder::default(),
       vars: ScopeBuilder::default(),
       rules: Vec::new(),
       nets: Vec::new(),
-    };
-    parser.bump()?;
-    while parser.state.token.is_some() {
-      parser.parse_def()?;
+      declared: false,
+      undefined: Vec::new(),
+      last_line: (0, 1),
     }
+  }
+
+  pub fn parse(src: &'src str) -> Parse<'src, System> {
+    let mut parser = SimplicityParser::new(src);
+    parser.load(src)?;
     Ok(System { agents: parser.agents.finish(), rules: parser.rules, nets: parser.nets })
   }
 
-  fn parse_def(&mut self) -> Parse<'src, ()> {
+  /// Parses the definitions of `src`, keeping everything defined so far. The
+  /// agents are declared first, so that rules and nets may use agents defined
+  /// further down, and then the rest is parsed and every use of an undefined
+  /// agent is reported.
+  pub fn load(&mut self, src: &'src str) -> Parse<'src, ()> {
+    self.declare_agents(src)?;
+    self.restart(src)?;
+    self.declared = true;
+    let result = self.parse_defs();
+    self.declared = false;
+    result?;
+    self.report_undefined()
+  }
+
+  /// Defines every agent of `src`, skipping over everything else.
+  fn declare_agents(&mut self, src: &'src str) -> Parse<'src, ()> {
+    for (token, span) in Token::lexer(src).spanned() {
+      if token == Ok(Token::Agent) {
+        self.restart(&src[span.start..])?;
+        self.parse_agent_def(Vec::new())?;
+      }
+    }
+    Ok(())
+  }
+
+  /// Reports the undefined agents used since the last call, if any.
+  pub fn report_undefined(&mut self) -> Parse<'src, ()> {
+    if self.undefined.is_empty() {
+      return Ok(());
+    }
+    Err(self.undefined_agents())
+  }
+
+  /// The undefined agents used since the last call, each with the name of a
+  /// similar agent if there is one.
+  pub fn undefined_agents(&mut self) -> ParseError<'src> {
+    let names = std::mem::take(&mut self.undefined)
+      .into_iter()
+      .map(|name| (name, self.similar_agent(name)))
+      .collect();
+    ParseError::UndefinedAgents { names }
+  }
+
+  /// The agent whose name is closest to `name`, if any is close enough to be
+  /// a likely misspelling.
+  fn similar_agent(&self, name: &str) -> Option<&'src str> {
+    let limit = (name.chars().count() / 3).max(1);
+    self
+      .agents
+      .lookup
+      .keys()
+      .map(|&x| (edit_distance(name, x), x))
+      .filter(|&(distance, _)| distance <= limit)
+      .min()
+      .map(|(_, x)| x)
+  }
+
+  /// The agent called `name`, if it is defined. An undefined name is recorded
+  /// to be reported once parsing is done.
+  fn resolve_agent(&mut self, name: &'src str) -> Option<Agent> {
+    let agent = self.agents.get(name);
+    if agent.is_none() && !self.undefined.contains(&name) {
+      self.undefined.push(name);
+    }
+    agent
+  }
+
+  /// Continues parsing from the start of `src`, keeping everything defined so
+  /// far.
+  pub fn restart(&mut self, src: &'src str) -> Parse<'src, ()> {
+    self.state = ParserState::new(src);
+    self.last_line = (0, 1);
+    self.bump()
+  }
+
+  pub fn parse_defs(&mut self) -> Parse<'src, ()> {
+    while self.state.token.is_some() {
+      self.parse_def()?;
+    }
+    Ok(())
+  }
+
+  pub fn parse_def(&mut self) -> Parse<'src, ()> {
+    let attributes = self.parse_attributes()?;
     if self.check(Token::Agent) {
-      self.parse_agent_def()?;
+      if let Some((name, _)) = attributes.expect {
+        return Err(ParseError::MisplacedAttribute { name });
+      }
+      self.check_lints(&attributes, Token::Agent)?;
+      self.parse_agent_def(attributes.allow)
+    } else if self.check(Token::Rule) {
+      self.check_lints(&attributes, Token::Rule)?;
+      self.parse_rule_def(attributes)
+    } else if self.check(Token::Net) {
+      self.check_lints(&attributes, Token::Net)?;
+      self.parse_net_def(attributes)
+    } else {
+      Err(self.unexpected_error())
     }
-    if self.check(Token::Rule) {
-      self.parse_rule_def()?;
+  }
+
+  /// Parses the attributes before a definition, such as
+  /// `#[expect(non_simple)]` or `#[allow(single_use_var, rule_never_fires)]`.
+  fn parse_attributes(&mut self) -> Parse<'src, Attributes<'src>> {
+    let mut attributes = Attributes::default();
+    while self.eat(Token::Hash)? {
+      self.expect(Token::OpenBracket)?;
+      let name = self.expect(Token::Ident)?;
+      match name {
+        "expect" => {
+          self.expect(Token::OpenParen)?;
+          let verdict = self.parse_verdict()?;
+          self.expect(Token::CloseParen)?;
+          if attributes.expect.is_some() {
+            return Err(ParseError::DuplicateExpectation { name });
+          }
+          attributes.expect = Some((name, verdict));
+        }
+        "allow" => {
+          let lints = self.parse_delimited(
+            Delimiters {
+              open: Some(Token::OpenParen),
+              close: Some(Token::CloseParen),
+              separator: Some(Token::Comma),
+            },
+            |self_| {
+              let name = self_.expect(Token::Ident)?;
+              Ok((name, Lint::from_name(name).ok_or(ParseError::UnknownLint { name })?))
+            },
+          )?;
+          for (name, lint) in lints {
+            attributes.lints.push(name);
+            attributes.allow.push(lint);
+          }
+        }
+        _ => return Err(ParseError::UnknownAttribute { name }),
+      }
+      self.expect(Token::CloseBracket)?;
     }
-    if self.check(Token::Net) {
-      self.parse_net_def()?;
+    Ok(attributes)
+  }
+
+  /// Checks that each lint allowed by `attributes` can be reported for the
+  /// definition they are attached to, which starts with `keyword`.
+  fn check_lints(&self, attributes: &Attributes<'src>, keyword: Token) -> Parse<'src, ()> {
+    for (&name, &lint) in attributes.lints.iter().zip(&attributes.allow) {
+      let applies = match lint {
+        Lint::UnusedAgent | Lint::AgentWithoutRules => keyword == Token::Agent,
+        Lint::RuleNeverFires => keyword == Token::Rule,
+        Lint::DisconnectedNet => keyword == Token::Net,
+        Lint::SingleUseVar => keyword != Token::Agent,
+      };
+      if !applies {
+        return Err(ParseError::MisplacedLint { name, item: self.state.lexer.slice() });
+      }
     }
     Ok(())
   }
 
+  fn parse_verdict(&mut self) -> Parse<'src, Verdict> {
+    let name = self.expect(Token::Ident)?;
+    Verdict::from_keyword(name).ok_or(ParseError::UnknownVerdict { name })
+  }
+
+  /// Parses the optional `: verdict` suffix of a rule or net, combining it
+  /// with the verdict expected by its attributes.
+  fn parse_expect_suffix(
+    &mut self,
+    expect: Option<(&'src str, Verdict)>,
+  ) -> Parse<'src, Option<Verdict>> {
+    if !self.eat(Token::Colon)? {
+      return Ok(expect.map(|(_, verdict)| verdict));
+    }
+    let verdict = self.parse_verdict()?;
+    match expect {
+      Some((name, _)) => Err(ParseError::DuplicateExpectation { name }),
+      None => Ok(Some(verdict)),
+    }
+  }
+
   fn parse_partition<T>(
     &mut self,
     outer_delims: Delimiters<Token>,
-    mut parse_el: impl FnMut(&mut Self) -> Parse<'src, T>,
-  ) -> Parse<'src, Vec<Vec<T>>> {
+    parse_el: &mut impl FnMut(&mut Self) -> Parse<'src, T>,
+  ) -> Parse<'src, Vec<Partition<T>>> {
     self.parse_delimited(outer_delims, |self_| {
       if self_.eat(Token::OpenBrace)? {
-        self_.parse_delimited(
+        let parts = self_.parse_partition(
           Delimiters { open: None, close: Some(Token::CloseBrace), separator: Some(Token::Comma) },
-          &mut parse_el,
-        )
+          parse_el,
+        )?;
+        Ok(Partition::Group(parts))
       } else {
-        Ok(vec![parse_el(self_)?])
+        Ok(Partition::Port(parse_el(self_)?))
       }
     })
   }
 
-  fn parse_agent_def(&mut self) -> Parse<'src, ()> {
+  fn parse_agent_def(&mut self, allow: Vec<Lint>) -> Parse<'src, ()> {
     self.expect(Token::Agent)?;
     let name = self.expect(Token::Ident)?;
     self.expect(Token::OpenParen)?;
     self.expect(Token::Star)?;
+    let mut names = Vec::new();
     let auxiliary = if self.eat(Token::Comma)? {
       self.parse_partition(
         Delimiters { open: None, close: Some(Token::CloseParen), separator: Some(Token::Comma) },
-        |self_| {
-          self_.expect(Token::Star)?;
-          Ok(())
+        &mut |self_| {
+          if self_.eat(Token::Star)? {
+            return Ok(None);
+          }
+          let name = self_.expect(Token::Ident)?;
+          if names.contains(&name) {
+            return Err(ParseError::DuplicatePort { name });
+          }
+          names.push(name);
+          Ok(Some(name.to_owned()))
         },
       )?
     } else {
       self.expect(Token::CloseParen)?;
       Vec::new()
     };
-    self
-      .agents
-      .define(name, AgentDef { auxiliary })
-      .map_err(|_| ParseError::DuplicateAgentDef { name })?;
+    let mut def = AgentDef { auxiliary, axioms: Vec::new(), allow };
+    if self.check(Token::OpenBrace) {
+      def.axioms = self.parse_delimited(
+        Delimiters {
+          open: Some(Token::OpenBrace),
+          close: Some(Token::CloseBrace),
+          separator: Some(Token::Comma),
+        },
+        |self_| {
+          let a = self_.parse_port_ref(name, &def)?;
+          let arrow = self_.parse_arrow()?;
+          let port = self_.state.lexer.slice();
+          let b = self_.parse_port_ref(name, &def)?;
+          if a == b {
+            return Err(ParseError::ReflexiveAxiom { port });
+          }
+          Ok(Axiom { a, arrow, b })
+        },
+      )?;
+      // Axioms can only narrow what the partition allows; a node whose ports
+      // can be related in no way at all could never be part of a simple net.
+      let mut diagram = Diagram::default();
+      diagram.add_agent(&Node { agent: Agent(0), ports: Vec::new(), span: None }, &def);
+      if Verdict::of(&mut diagram) == Verdict::NonSimple {
+        return Err(ParseError::ContradictoryAxioms { agent: name });
+      }
+    }
+    if self.declared {
+      // The agent was defined by `declare_agents`, which skips attributes.
+      let agent = self.agents.get(name).unwrap();
+      self.agents.scope.defs[agent].value.allow = def.allow;
+    } else {
+      self.agents.define(name, def).map_err(|_| ParseError::DuplicateAgentDef { name })?;
+    }
     Ok(())
   }
 
-  fn parse_rule_def(&mut self) -> Parse<'src, ()> {
+  /// Parses a reference to a port of `def`, the agent called `agent`, by its
+  /// name or its index among the ports of a node.
+  fn parse_port_ref(&mut self, agent: &'src str, def: &AgentDef) -> Parse<'src, usize> {
+    let port = if self.check(Token::Num) {
+      let name = self.expect(Token::Num)?;
+      name.parse().ok().filter(|&i| i < def.arity()).ok_or(name)
+    } else {
+      let name = self.expect(Token::Ident)?;
+      def.port(name).ok_or(name)
+    };
+    port.map_err(|name| ParseError::UnknownPort { agent, name })
+  }
+
+  fn parse_rule_def(&mut self, attributes: Attributes<'src>) -> Parse<'src, ()> {
     self.expect(Token::Rule)?;
     let a = self.parse_node()?;
     let b = self.parse_node()?;
-    let result = self.parse_net()?;
-    self.rules.push(RuleDef { vars: self.vars.finish(), a, b, result });
+    let expect = self.parse_expect_suffix(attributes.expect)?;
+    let (Some(a), Some(b)) = (a, b) else {
+      // The rule is left out, as its undefined agents will be reported, but its
+      // body is still parsed for errors of its own.
+      self.parse_net(Vec::new())?;
+      self.vars.finish();
+      return Ok(());
+    };
+    // The principal ports of the active pair are not part of the rule's diagram.
+    let ports = [&a, &b].into_iter().flat_map(|node| node.auxiliary().iter().copied()).collect();
+    let (result, constraints) = self.parse_net(ports)?;
+    self.rules.push(RuleDef {
+      vars: self.vars.finish(),
+      a,
+      b,
+      result,
+      constraints,
+      expect,
+      allow: attributes.allow,
+    });
     Ok(())
   }
 
-  fn parse_net(&mut self) -> Parse<'src, Vec<Node>> {
-    self.parse_delimited(
-      Delimiters { open: Some(Token::OpenBrace), close: Some(Token::CloseBrace), separator: None },
-      Self::parse_node,
-    )
+  /// Parses the body of a rule or net: its nodes, and its assumptions and
+  /// assertions, which may only mention variables connected to a port, either
+  /// of a node of the body or one of `ports`.
+  fn parse_net(&mut self, ports: Vec<Var>) -> Parse<'src, (Vec<Node>, Vec<Constraint>)> {
+    let mut nodes = Vec::new();
+    let mut constraints = Vec::new();
+    let mut mentioned = Vec::new();
+    self.expect(Token::OpenBrace)?;
+    while !self.eat(Token::CloseBrace)? {
+      let kind = if self.eat(Token::Assume)? {
+        ConstraintKind::Assume
+      } else if self.eat(Token::Assert)? {
+        ConstraintKind::Assert
+      } else {
+        let name = self.expect(Token::Ident)?;
+        self.parse_term(name, None, &mut nodes)?;
+        continue;
+      };
+      let a = self.expect(Token::Ident)?;
+      let arrow = self.parse_arrow()?;
+      let b = self.expect(Token::Ident)?;
+      if a == b {
+        return Err(ParseError::ReflexiveConstraint { name: b });
+      }
+      mentioned.extend([a, b]);
+      let [a, b] = [a, b].map(|name| self.vars.get_or_define(name, ()));
+      constraints.push(Constraint { kind, a, arrow, b });
+    }
+    // The ports of nodes of undefined agents are unknown, so nothing is
+    // checked once one has been used; the agent will be reported instead.
+    for name in mentioned.into_iter().filter(|_| self.undefined.is_empty()) {
+      let var = self.vars.get(name).unwrap();
+      if !ports.contains(&var) && !nodes.iter().any(|node| node.ports.contains(&var)) {
+        return Err(ParseError::UnconnectedVar { name });
+      }
+    }
+    Ok((nodes, constraints))
+  }
+
+  /// Parses a relation between two variables, written with the glyphs of
+  /// `Arrow`'s `Debug` impl or as atoms joined by `|`.
+  fn parse_arrow(&mut self) -> Parse<'src, Arrow> {
+    let start = self.state.lexer.span().start;
+    let mut end = start;
+    let mut glyphs = String::new();
+    while self.check(Token::Glyph) || self.check(Token::Star) || self.check(Token::Minus) {
+      glyphs += self.state.lexer.slice();
+      end = self.state.lexer.span().end;
+      self.bump()?;
+    }
+    let src = &self.state.lexer.source()[start..end];
+    if glyphs.is_empty() {
+      return Err(self.unexpected_error());
+    }
+    Arrow::parse_glyphs(&glyphs).ok_or(ParseError::InvalidArrow { arrow: src })
+  }
+
+  /// Parses the rest of a node of a net, after its agent's `name`, adding it
+  /// and the nodes nested in its ports to `nodes` in the order they are
+  /// written.
+  ///
+  /// A nested node connects to the port it is written in, `principal`, through
+  /// its principal port, which is written `_`, as in `Succ(out, Succ(_, Zero))`;
+  /// a nested node of an agent without auxiliary ports may be written without
+  /// parentheses. Each nested node is connected by a fresh variable, named with
+  /// a `~` so that it cannot clash with those in the source.
+  fn parse_term(
+    &mut self,
+    name: &'src str,
+    principal: Option<Var>,
+    nodes: &mut Vec<Node>,
+  ) -> Parse<'src, ()> {
+    let agent = self.resolve_agent(name);
+    let index = nodes.len();
+    let ports = match principal {
+      Some(principal) if !self.check(Token::OpenParen) => vec![principal],
+      _ => self.parse_args(name, agent, |self_, i, port| match principal {
+        Some(principal) if i == 0 => {
+          if port != "_" {
+            return Err(ParseError::NestedPrincipal { name: port });
+          }
+          Ok(principal)
+        }
+        _ => self_.parse_port(port, nodes),
+      })?,
+    };
+    if let Some(agent) = agent {
+      let span = Some(self.span(name));
+      nodes.insert(index, Node { agent, ports, span });
+    }
+    Ok(())
   }
 
-  fn parse_node(&mut self) -> Parse<'src, Node> {
+  /// Parses the rest of a port of a node of a net, starting with `name`:
+  /// either a variable, or a nested node. A name followed by parentheses is a
+  /// nested node, as is the name of an agent without auxiliary ports; any
+  /// other name, even that of an agent, is a variable.
+  fn parse_port(&mut self, name: &'src str, nodes: &mut Vec<Node>) -> Parse<'src, Var> {
+    let nullary =
+      self.agents.get(name).is_some_and(|x| self.agents.scope.defs[x].value.arity() == 1);
+    if !self.check(Token::OpenParen) && !nullary {
+      return Ok(self.vars.get_or_define(name, ()));
+    }
+    let var = self.vars.scope.define(format!("~{}", self.vars.scope.defs.len()), ());
+    self.parse_term(name, Some(var), nodes)?;
+    Ok(var)
+  }
+
+  /// Parses a node whose ports are all variables, or `None` if its agent is
+  /// undefined, which is recorded to be reported.
+  pub fn parse_node(&mut self) -> Parse<'src, Option<Node>> {
     let name = self.expect(Token::Ident)?;
-    let agent = self.agents.get(name).ok_or(ParseError::UndefinedAgent { name })?;
-    let ports = self.parse_delimited(
+    let agent = self.resolve_agent(name);
+    let ports =
+      self.parse_args(name, agent, |self_, _, name| Ok(self_.vars.get_or_define(name, ())))?;
+    let span = Some(self.span(name));
+    Ok(agent.map(|agent| Node { agent, ports, span }))
+  }
+
+  /// The span of `slice`, which is part of the source being parsed.
+  fn span(&mut self, slice: &str) -> Span {
+    let src = self.state.lexer.source();
+    let start = slice.as_ptr() as usize - src.as_ptr() as usize;
+    // Spans are mostly taken in order, but a node takes its span after those
+    // nested in it.
+    let (last, line) = self.last_line;
+    let line = if start >= last {
+      line + src[last..start].matches('\n').count()
+    } else {
+      line - src[start..last].matches('\n').count()
+    };
+    self.last_line = (start, line);
+    Span { start, end: start + slice.len(), line }
+  }
+
+  /// Parses the parenthesized ports of a node of `agent`, called `name`, and
+  /// puts them in the agent's order. Ports are given in order, optionally
+  /// followed by auxiliary ports given by name, as in `Cons(x, tail: t)`.
+  ///
+  /// `parse_port` parses the rest of the `i`th port, given the identifier it
+  /// starts with.
+  fn parse_args(
+    &mut self,
+    name: &'src str,
+    agent: Option<Agent>,
+    mut parse_port: impl FnMut(&mut Self, usize, &'src str) -> Parse<'src, Var>,
+  ) -> Parse<'src, Vec<Var>> {
+    let mut ports = Vec::new();
+    let mut named = Vec::new();
+    self.parse_delimited(
       Delimiters {
         open: Some(Token::OpenParen),
         close: Some(Token::CloseParen),
         separator: Some(Token::Comma),
       },
-      Self::parse_var,
+      |self_| {
+        let first = self_.expect(Token::Ident)?;
+        if self_.eat(Token::Colon)? {
+          // The ports of an undefined agent are left unchecked, as it will be
+          // reported anyway, and taken in the order they are given.
+          let i = match agent {
+            Some(agent) => self_.agents.scope.defs[agent]
+              .value
+              .port(first)
+              .ok_or(ParseError::UnknownPort { agent: name, name: first })?,
+            None => ports.len() + named.len(),
+          };
+          let port = self_.expect(Token::Ident)?;
+          named.push((first, i, parse_port(self_, i, port)?));
+        } else if let Some(&(name, ..)) = named.last() {
+          return Err(ParseError::PositionalAfterNamed { name });
+        } else {
+          ports.push(parse_port(self_, ports.len(), first)?);
+        }
+        Ok(())
+      },
     )?;
-    Ok(Node { agent, ports })
+    if named.is_empty() {
+      return Ok(ports);
+    }
+
+    let Some(agent) = agent else { return Ok(ports) };
+    let def = &self.agents.scope.defs[agent].value;
+    let mut slots = ports.into_iter().map(Some).collect::<Vec<_>>();
+    slots.resize(slots.len().max(def.arity()), None);
+    for (port, i, var) in named {
+      if slots[i].replace(var).is_some() {
+        return Err(ParseError::DuplicatePort { name: port });
+      }
+    }
+    let missing = slots.iter().position(Option::is_none);
+    if let Some(i) = missing {
+      return Err(ParseError::MissingPort { agent: name, port: def.describe_port(i) });
+    }
+    Ok(slots.into_iter().flatten().collect())
   }
 
   fn parse_var(&mut self) -> Parse<'src, Var> {
@@ -150,19 +609,245 @@ impl<'src> SimplicityParser<'src> {
     Ok(self.vars.get_or_define(name, ()))
   }
 
-  fn parse_net_def(&mut self) -> Parse<'src, ()> {
-    self.expect(Token::Net)?;
-    let name = self.expect(Token::Ident)?;
-    let ports = self.parse_partition(
+  pub fn parse_ports(&mut self) -> Parse<'src, Vec<Partition<Var>>> {
+    self.parse_partition(
       Delimiters {
         open: Some(Token::OpenParen),
         close: Some(Token::CloseParen),
         separator: Some(Token::Comma),
       },
-      Self::parse_var,
-    )?;
-    let nodes = self.parse_net()?;
-    self.nets.push(NetDef { name: name.to_owned(), vars: self.vars.finish(), ports, nodes });
+      &mut Self::parse_var,
+    )
+  }
+
+  fn parse_net_def(&mut self, attributes: Attributes<'src>) -> Parse<'src, ()> {
+    self.expect(Token::Net)?;
+    let name = self.expect(Token::Ident)?;
+    let ports = self.parse_ports()?;
+    let expect = self.parse_expect_suffix(attributes.expect)?;
+    let free = ports.iter().flat_map(Partition::ports).copied().collect();
+    let (nodes, constraints) = self.parse_net(free)?;
+    self.nets.push(NetDef {
+      name: name.to_owned(),
+      vars: self.vars.finish(),
+      ports,
+      nodes,
+      constraints,
+      expect,
+      allow: attributes.allow,
+    });
     Ok(())
   }
 }
+
+/// The number of characters to insert, delete or substitute to turn `a` into
+/// `b`.
+fn edit_distance(a: &str, b: &str) -> usize {
+  let b = b.chars().collect::<Vec<_>>();
+  let mut row = (0..=b.len()).collect::<Vec<_>>();
+  for (i, x) in a.chars().enumerate() {
+    let mut diagonal = row[0];
+    row[0] = i + 1;
+    for (j, &y) in b.iter().enumerate() {
+      let substitute = diagonal + (x != y) as usize;
+      diagonal = row[j + 1];
+      row[j + 1] = substitute.min(row[j] + 1).min(diagonal + 1);
+    }
+  }
+  row[b.len()]
+}
+
+#[test]
+fn desugars_nested_terms() {
+  let src = "
+    agent Succ(*, *)
+    agent Zero(*)
+    agent Pair(*, *, *)
+    rule Pair(_, a, b) Zero(_) { Pair(a, Succ(_, Zero), Succ(_, b)) }
+    net two(out) { Succ(out, Succ(_, Zero)) }
+  ";
+  let system = SimplicityParser::parse(src).unwrap();
+  let shown = system.to_string();
+  let rule = "
+rule Pair(_, a, b) Zero(_) {
+  Pair(a, ~3, ~5)
+  Succ(~3, ~4)
+  Zero(~4)
+  Succ(~5, b)
+}
+";
+  let net = "
+net two(out) {
+  Succ(out, ~1)
+  Succ(~1, ~2)
+  Zero(~2)
+}
+";
+  assert!(shown.contains(rule) && shown.contains(net), "{shown}");
+  // The desugared system reads back as the same system.
+  assert_eq!(
+    SimplicityParser::parse(&shown.replace('~', "t")).unwrap().to_string(),
+    shown.replace('~', "t")
+  );
+}
+
+#[test]
+fn rejects_named_principals_of_nested_terms() {
+  let src = "agent Succ(*, *) net n(out) { Succ(out, Succ(x, y)) }";
+  assert!(matches!(SimplicityParser::parse(src), Err(ParseError::NestedPrincipal { name: "x" })));
+}
+
+#[test]
+fn connects_ports_by_name() {
+  let src = "
+    agent Cons(*, head, tail)
+    agent Disj(*, {left, right})
+    net n(x, h, t, l, r) { Cons(x, tail: t, head: h) Disj(x, l, right: r) }
+  ";
+  let system = SimplicityParser::parse(src).unwrap();
+  let shown = system.to_string();
+  assert!(shown.starts_with("agent Cons(*, head, tail)\nagent Disj(*, {left, right})\n"));
+  assert!(shown.contains("  Cons(x, h, t)\n  Disj(x, l, r)\n"), "{shown}");
+  let cons = &system.agents.defs[Agent(0)].value;
+  assert_eq!(cons.port("tail"), Some(2));
+  assert_eq!(
+    [0, 1, 2].map(|i| cons.describe_port(i)),
+    ["the principal port", "port `head`", "port `tail`"]
+  );
+}
+
+#[test]
+fn rejects_misnamed_ports() {
+  let agents = "agent Cons(*, head, tail) agent Pair(*, *, *)";
+  let error = |net| {
+    let src = format!("{agents} net n(x, y, z) {{ {net} }}");
+    format!("{:?}", SimplicityParser::parse(&src).unwrap_err())
+  };
+  assert_eq!(error("Cons(x, head: y, rest: z)"), r#"UnknownPort { agent: "Cons", name: "rest" }"#);
+  assert_eq!(error("Cons(x, y, head: z)"), r#"DuplicatePort { name: "head" }"#);
+  assert_eq!(error("Cons(x, tail: z, y)"), r#"PositionalAfterNamed { name: "tail" }"#);
+  assert_eq!(
+    error("Cons(x, tail: z) Pair(y, y, z)"),
+    r#"MissingPort { agent: "Cons", port: "port `head`" }"#
+  );
+  let src = format!("{agents} net n(x, y, z) {{ Pair(x, y, 2: z) }}");
+  assert!(matches!(
+    SimplicityParser::parse(&src),
+    Err(ParseError::UnexpectedToken { found: "2", .. })
+  ));
+}
+
+#[test]
+fn parses_constraints() {
+  let src = "
+    agent A(*, *)
+    agent B(*)
+    rule A(_, x) B(_) { B(y) B(x) assume x --*-- y }
+    net n(r, s) { A(r, t) B(t) assert r <<*>> s assume s -<--- t }
+  ";
+  let shown = SimplicityParser::parse(src).unwrap().to_string();
+  assert!(shown.contains("  B(x)\n  assume x --*-- y\n}"), "{shown}");
+  assert!(shown.contains("  assert r <<*>> s\n  assume s -<--- t\n}"), "{shown}");
+}
+
+#[test]
+fn rejects_constraints_on_unconnected_vars() {
+  let error = |body| {
+    let src = format!("agent A(*, *) agent B(*) rule A(_, x) B(_) {{ {body} }}");
+    format!("{:?}", SimplicityParser::parse(&src).unwrap_err())
+  };
+  // The principal ports of the active pair are not in the rule's diagram.
+  assert_eq!(error("B(x) assume _ --*-- x"), r#"UnconnectedVar { name: "_" }"#);
+  assert_eq!(error("B(x) assert x --*-- y"), r#"UnconnectedVar { name: "y" }"#);
+  let src = "agent B(*) net n(r) { B(r) assume q --*-- r }";
+  assert!(matches!(SimplicityParser::parse(src), Err(ParseError::UnconnectedVar { name: "q" })));
+}
+
+#[test]
+fn rejects_reflexive_constraints() {
+  let error = |src| format!("{:?}", SimplicityParser::parse(src).unwrap_err());
+  assert_eq!(
+    error("agent B(*) net n(r) { B(r) assume r <<--- r }"),
+    r#"ReflexiveConstraint { name: "r" }"#
+  );
+  assert_eq!(
+    error("agent A(*, *) agent B(*) rule A(_, x) B(_) { B(x) assert x --*-- x }"),
+    r#"ReflexiveConstraint { name: "x" }"#
+  );
+}
+
+#[test]
+fn rejects_degenerate_axioms() {
+  let error = |src| format!("{:?}", SimplicityParser::parse(src).unwrap_err());
+  assert_eq!(error("agent A(*, x, y) { x <<--- x }"), r#"ReflexiveAxiom { port: "x" }"#);
+  assert_eq!(error("agent A(*, x) { 0 --*-- 0 }"), r#"ReflexiveAxiom { port: "0" }"#);
+  // The principal port of a node is always on the `>` side of its auxiliary
+  // ports.
+  assert_eq!(error("agent A(*, x) { 0 <<*-- x }"), r#"ContradictoryAxioms { agent: "A" }"#);
+  // Ports in different groups always overlap.
+  assert_eq!(error("agent B(*, x, y) { x <<--- y }"), r#"ContradictoryAxioms { agent: "B" }"#);
+  assert!(SimplicityParser::parse("agent A(*, x) { 0 --->> x }").is_ok());
+}
+
+#[test]
+fn reports_each_undefined_agent_once() {
+  let src = "
+    agent Con(*, *)
+    rule Con(_, x) Foo(_, y) { Con(x, y) assume x --*-- y }
+    net n(x) { Cn(x, Cn(_, y)) Cn(z, tail: w) assert y --*-- z }
+  ";
+  let Err(ParseError::UndefinedAgents { names }) = SimplicityParser::parse(src) else { panic!() };
+  assert_eq!(names, [("Foo", None), ("Cn", Some("Con"))]);
+}
+
+#[test]
+fn rejects_lints_for_other_items() {
+  let error = |src| format!("{:?}", SimplicityParser::parse(src).unwrap_err());
+  assert_eq!(
+    error("#[allow(single_use_var)] agent A(*)"),
+    r#"MisplacedLint { name: "single_use_var", item: "agent" }"#
+  );
+  assert_eq!(
+    error("agent A(*) #[allow(unused_agent, disconnected_net)] rule A(_) A(_) {}"),
+    r#"MisplacedLint { name: "unused_agent", item: "rule" }"#
+  );
+  assert_eq!(
+    error("agent A(*) #[allow(single_use_var, rule_never_fires)] net n(x) { A(x) }"),
+    r#"MisplacedLint { name: "rule_never_fires", item: "net" }"#
+  );
+}
+
+#[test]
+fn numbers_lines_of_nested_nodes() {
+  let src = "agent Succ(*, *)\nagent Zero(*)\nnet n(out) {\n  Succ(out,\n    Succ(_, Zero))\n  Zero(x)\n}\n";
+  let system = SimplicityParser::parse(src).unwrap();
+  let spans = system.nets[0].nodes.iter().map(|node| node.span.unwrap()).collect::<Vec<_>>();
+  assert_eq!(spans.iter().map(|span| span.line).collect::<Vec<_>>(), [4, 5, 5, 6]);
+  let names = spans.iter().map(|span| &src[span.start..span.end]).collect::<Vec<_>>();
+  assert_eq!(names, ["Succ", "Succ", "Zero", "Zero"]);
+}
+
+#[test]
+fn takes_bare_names_of_agents_with_ports_as_vars() {
+  let src = "
+    agent A(*, *)
+    agent Nil(*)
+    net n(x) { A(x, A) A(A, Nil) }
+  ";
+  let system = SimplicityParser::parse(src).unwrap();
+  assert_eq!(
+    system.to_string().split_once("net ").unwrap().1,
+    "n(x) {\n  A(x, A)\n  A(A, ~2)\n  Nil(~2)\n}\n"
+  );
+}
+
+#[test]
+fn parses_active_pairs_without_ports() {
+  let system = SimplicityParser::parse("agent A(*, *) agent B(*) rule A() B() {}").unwrap();
+  let rule = &system.rules[0];
+  assert!(rule.a.ports.is_empty() && rule.b.ports.is_empty());
+  assert_eq!(
+    crate::check::Item::Rule(0).arity_errors(&system),
+    ["agent `A(*, *)` has 2 ports, but 0 were given", "agent `B(*)` has 1 ports, but 0 were given"]
+  );
+}
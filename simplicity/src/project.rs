@@ -0,0 +1,286 @@
+use std::{
+  fs::{read_dir, read_to_string},
+  path::{Path, PathBuf},
+};
+
+use crate::{ast::Verdict, check::Item, parser::SimplicityParser, toml::Document};
+
+/// The settings of a project, read from `simplicity.toml` at its root:
+///
+/// ```toml
+/// include = ["examples", "tests"]  # where to look for .nets files
+/// ignore = ["**/scratch/*.nets"]    # files to skip
+///
+/// [expect-non-simple]
+/// "examples/basic.nets" = ["net disj_conj_conj"]
+/// ```
+#[derive(Debug, Default)]
+pub struct Config {
+  pub include: Vec<String>,
+  pub ignore: Vec<String>,
+  pub expect_non_simple: Vec<(String, Vec<String>)>,
+}
+
+impl Config {
+  pub fn load(root: &Path) -> Result<Config, String> {
+    let path = root.join("simplicity.toml");
+    let Ok(src) = read_to_string(&path) else {
+      return Ok(Config { include: vec![".".into()], ..Default::default() });
+    };
+    let error = |message: String| format!("{}: {message}", path.display());
+    let document = Document::parse(&src).map_err(error)?;
+    let strings = |key: &str| match document.get("", key) {
+      None => Ok(Vec::new()),
+      Some(value) => value
+        .as_strings()
+        .map(|x| x.into_iter().map(str::to_owned).collect())
+        .ok_or_else(|| error(format!("`{key}` must be an array of strings"))),
+    };
+    let mut config =
+      Config { include: strings("include")?, ignore: strings("ignore")?, ..Default::default() };
+    if config.include.is_empty() {
+      config.include.push(".".into());
+    }
+    for (file, items) in document.table("expect-non-simple") {
+      let items =
+        items.as_strings().ok_or_else(|| error(format!("`{file}` must be an array of strings")))?;
+      config.expect_non_simple.push((file.clone(), items.into_iter().map(str::to_owned).collect()));
+    }
+    Ok(config)
+  }
+
+  fn expects_non_simple(&self, file: &str, item: &str) -> bool {
+    self.expect_non_simple.iter().any(|(x, items)| x == file && items.iter().any(|x| x == item))
+  }
+}
+
+/// The outcome of checking one file of a project.
+#[derive(Debug, Default)]
+pub struct FileReport {
+  pub path: String,
+  pub simple: usize,
+  pub non_simple: usize,
+  pub expected: usize,
+  /// Items whose verdict was not the expected one, and any errors.
+  pub unexpected: Vec<String>,
+}
+
+/// Every `.nets` file under the included paths of `root`, relative to it and
+/// in sorted order, leaving out ignored files.
+pub fn files(root: &Path, config: &Config) -> Vec<String> {
+  let mut files = Vec::new();
+  for include in &config.include {
+    collect(&root.join(include), &mut files);
+  }
+  let mut files = files
+    .into_iter()
+    .filter_map(|path| {
+      let path = path.strip_prefix(root).ok()?.to_string_lossy().replace('\\', "/");
+      let path = path.trim_start_matches("./").to_owned();
+      (!config.ignore.iter().any(|glob| glob_matches(glob, &path))).then_some(path)
+    })
+    .collect::<Vec<_>>();
+  files.sort();
+  files.dedup();
+  files
+}
+
+fn collect(path: &Path, files: &mut Vec<PathBuf>) {
+  if path.is_dir() {
+    let Ok(entries) = read_dir(path) else { return };
+    for entry in entries.flatten() {
+      // Links are not followed, since one to a directory above it would make
+      // the walk endless.
+      if entry.file_type().is_ok_and(|x| !x.is_symlink()) {
+        collect(&entry.path(), files);
+      }
+    }
+  } else if path.extension().is_some_and(|x| x == "nets") {
+    files.push(path.to_owned());
+  }
+}
+
+/// Matches a path against a glob in which `*` stands for any part of a single
+/// component and `**` for any number of components.
+pub fn glob_matches(glob: &str, path: &str) -> bool {
+  fn components(glob: &[&str], path: &[&str]) -> bool {
+    match (glob, path) {
+      ([], []) => true,
+      (["**", rest @ ..], _) => (0..=path.len()).any(|i| components(rest, &path[i..])),
+      ([g, glob @ ..], [p, path @ ..]) => {
+        component(g.as_bytes(), p.as_bytes()) && components(glob, path)
+      }
+      _ => false,
+    }
+  }
+  fn component(glob: &[u8], name: &[u8]) -> bool {
+    match glob {
+      [] => name.is_empty(),
+      [b'*', rest @ ..] => (0..=name.len()).any(|i| component(rest, &name[i..])),
+      [c, rest @ ..] => name.first() == Some(c) && component(rest, &name[1..]),
+    }
+  }
+  let glob = glob.split('/').collect::<Vec<_>>();
+  let path = path.split('/').collect::<Vec<_>>();
+  components(&glob, &path)
+}
+
+pub fn check_file(root: &Path, path: &str, config: &Config) -> FileReport {
+  let mut report = FileReport { path: path.to_owned(), ..Default::default() };
+  let src = match read_to_string(root.join(path)) {
+    Ok(src) => src,
+    Err(error) => {
+      report.unexpected.push(format!("error: {error}"));
+      return report;
+    }
+  };
+  let system = match SimplicityParser::parse(&src) {
+    Ok(system) => system,
+    Err(error) => {
+      report.unexpected.push(format!("error: {error:?}"));
+      return report;
+    }
+  };
+  for item in Item::all(&system) {
+    let name = item.name(&system);
+    let errors = item.arity_errors(&system);
+    if !errors.is_empty() {
+      report.unexpected.push(format!("{name}: error: {}", errors.join("; ")));
+      continue;
+    }
+    let mut diagram = item.diagram(&system);
+    let verdict = Verdict::of(&mut diagram);
+    for failure in item.failed_assertions(&system, &diagram) {
+      report.unexpected.push(format!("{name}: {failure}"));
+    }
+    let expected = item.expect(&system).unwrap_or(if config.expects_non_simple(path, &name) {
+      Verdict::NonSimple
+    } else {
+      Verdict::Simple
+    });
+    match verdict {
+      Verdict::Simple => report.simple += 1,
+      Verdict::NonSimple => report.non_simple += 1,
+    }
+    if verdict == expected {
+      report.expected += 1;
+    } else {
+      report.unexpected.push(format!("{name}: {verdict}, expected {expected}"));
+    }
+  }
+  for (file, items) in &config.expect_non_simple {
+    for name in items.iter().filter(|_| file == path) {
+      if !Item::all(&system).any(|item| item.name(&system) == *name) {
+        report.unexpected.push(format!("{name}: expected non-simple, but not found"));
+      }
+    }
+  }
+  report
+}
+
+/// A fresh directory for a test, holding `files` with their contents.
+#[cfg(test)]
+fn temp_project(name: &str, files: &[(&str, &str)]) -> PathBuf {
+  let root = std::env::temp_dir().join(format!("simplicity-{name}-{}", std::process::id()));
+  let _ = std::fs::remove_dir_all(&root);
+  for (path, src) in files {
+    let path = root.join(path);
+    std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+    std::fs::write(path, src).unwrap();
+  }
+  root
+}
+
+#[test]
+fn loads_configs() {
+  let root = temp_project("config-default", &[]);
+  assert_eq!(Config::load(&root).unwrap().include, ["."]);
+
+  let toml = r#"
+    include = ["examples", "tests"]
+    ignore = ["**/scratch/*.nets"]
+
+    [expect-non-simple]
+    "examples/basic.nets" = ["net disj_conj_conj", "rule Con/Dup"]
+  "#;
+  let root = temp_project("config", &[("simplicity.toml", toml)]);
+  let config = Config::load(&root).unwrap();
+  assert_eq!(config.include, ["examples", "tests"]);
+  assert_eq!(config.ignore, ["**/scratch/*.nets"]);
+  assert!(config.expects_non_simple("examples/basic.nets", "rule Con/Dup"));
+  assert!(!config.expects_non_simple("examples/other.nets", "rule Con/Dup"));
+  std::fs::remove_dir_all(&root).unwrap();
+
+  let root = temp_project("config-invalid", &[("simplicity.toml", "include = \"examples\"")]);
+  let error = Config::load(&root).unwrap_err();
+  assert!(error.ends_with("simplicity.toml: `include` must be an array of strings"), "{error}");
+  std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn walks_included_directories() {
+  let sources = [
+    ("a.nets", ""),
+    ("sub/b.nets", ""),
+    ("sub/notes.txt", ""),
+    ("sub/scratch/c.nets", ""),
+    ("other/d.nets", ""),
+  ];
+  let root = temp_project("walk", &sources);
+  #[cfg(unix)]
+  std::os::unix::fs::symlink(&root, root.join("sub/loop")).unwrap();
+  let config = Config { include: vec![".".into()], ..Default::default() };
+  assert_eq!(files(&root, &config), ["a.nets", "other/d.nets", "sub/b.nets", "sub/scratch/c.nets"]);
+  let config = Config {
+    include: vec!["sub".into(), "a.nets".into(), "sub/b.nets".into()],
+    ignore: vec!["**/scratch/*.nets".into()],
+    ..Default::default()
+  };
+  assert_eq!(files(&root, &config), ["a.nets", "sub/b.nets"]);
+  std::fs::remove_dir_all(&root).unwrap();
+}
+
+#[test]
+fn matches_globs() {
+  assert!(glob_matches("*.nets", "a.nets"));
+  assert!(!glob_matches("*.nets", "sub/a.nets"));
+  assert!(glob_matches("**/*.nets", "a.nets"));
+  assert!(glob_matches("**/scratch/*.nets", "x/y/scratch/a.nets"));
+  assert!(!glob_matches("**/scratch/*.nets", "x/scratch/y/a.nets"));
+  assert!(glob_matches("sub/**", "sub/x/y"));
+  assert!(glob_matches("a*b*c", "abxbc"));
+  assert!(!glob_matches("a*b", "ab/b"));
+}
+
+#[test]
+fn checks_files_against_expectations() {
+  let src = "
+    agent Con(*, {*, *})
+    agent Era(*)
+    agent Fn(*, *, *)
+    net simple(r) { Con(r, a, b) Era(a) Era(b) }
+    net odd() { Fn(r, a, b) Fn(r, x, y) Fn(a, x, z) Fn(b, y, z) }
+  ";
+  let root = temp_project("check", &[("a.nets", src)]);
+  let verdict = |name| {
+    let system = SimplicityParser::parse(src).unwrap();
+    let item = Item::all(&system).find(|&x| x.name(&system) == name).unwrap();
+    item.check(&system)
+  };
+  assert_eq!(verdict("net simple"), Verdict::Simple);
+  assert_eq!(verdict("net odd"), Verdict::NonSimple);
+
+  let report = check_file(&root, "a.nets", &Config::default());
+  assert_eq!((report.simple, report.non_simple, report.expected), (1, 1, 1));
+  assert_eq!(report.unexpected, ["net odd: non-simple, expected simple"]);
+
+  let expect = vec![("a.nets".into(), vec!["net odd".into(), "net gone".into()])];
+  let config = Config { expect_non_simple: expect, ..Default::default() };
+  let report = check_file(&root, "a.nets", &config);
+  assert_eq!(report.expected, 2);
+  assert_eq!(report.unexpected, ["net gone: expected non-simple, but not found"]);
+
+  let report = check_file(&root, "missing.nets", &config);
+  assert!(report.unexpected[0].starts_with("error: "));
+  std::fs::remove_dir_all(&root).unwrap();
+}
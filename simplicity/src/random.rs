@@ -0,0 +1,419 @@
+use util::rng::Rng;
+
+#[cfg(test)]
+use std::ops::Range;
+
+#[cfg(test)]
+use util::graph::Graph;
+
+#[cfg(test)]
+use crate::{arrow::Arrow, ast::Verdict, check::Item, diagram::Diagram};
+use crate::{
+  ast::{Agent, AgentDef, NetDef, Node, Partition, RuleDef, System, Var},
+  scope::Scope,
+  suggest::partition,
+};
+
+/// Generates a well-formed system: every agent has a partitioned set of
+/// auxiliary ports, every node has the arity of its agent, and every variable
+/// is used exactly twice, counting the free ports of a net and the auxiliary
+/// ports of an active pair as uses.
+pub fn system(rng: &mut Rng) -> System {
+  let mut agents = Scope::default();
+  for i in 0..1 + rng.below(4) {
+    let ports = rng.below(5);
+    let partition = random_partition(rng, ports, 1);
+    agents.define(format!("A{i}"), AgentDef::default().regroup(&partition));
+  }
+  let mut system = System { agents, rules: Vec::new(), nets: Vec::new() };
+  for _ in 0..rng.below(3) {
+    let rule = rule(rng, &system);
+    system.rules.push(rule);
+  }
+  for i in 0..1 + rng.below(3) {
+    let net = net(rng, &system, format!("n{i}"));
+    system.nets.push(net);
+  }
+  system
+}
+
+fn rule(rng: &mut Rng, system: &System) -> RuleDef {
+  let mut vars = Scope::default();
+  let principal = vars.define("_".into(), ());
+  let mut nodes = Vec::new();
+  for _ in 0..2 {
+    let agent = Agent(rng.below(system.agents.defs.len()));
+    let arity = system.agents.defs[agent].value.arity();
+    nodes.push(Node { agent, ports: vec![principal; arity], span: None });
+  }
+  let result = random_nodes(rng, system, nodes.iter().map(|node| node.ports.len() - 1).sum());
+  nodes.extend(result);
+
+  // The auxiliary ports of the active pair are slots like any other, except
+  // that two of them wired together must share a variable in the pattern.
+  let mut slots = nodes
+    .iter()
+    .enumerate()
+    .flat_map(|(i, node)| (if i < 2 { 1 } else { 0 }..node.ports.len()).map(move |j| (i, j)))
+    .collect::<Vec<_>>();
+  rng.shuffle(&mut slots);
+  for pair in slots.chunks(2) {
+    let var = vars.define(format!("v{}", vars.defs.len() - 1), ());
+    for &(i, j) in pair {
+      nodes[i].ports[j] = var;
+    }
+  }
+
+  let mut nodes = nodes.into_iter();
+  let a = nodes.next().unwrap();
+  let b = nodes.next().unwrap();
+  RuleDef {
+    vars,
+    a,
+    b,
+    result: nodes.collect(),
+    constraints: Vec::new(),
+    expect: None,
+    allow: Vec::new(),
+  }
+}
+
+fn net(rng: &mut Rng, system: &System, name: String) -> NetDef {
+  let mut vars = Scope::default();
+  let mut nodes = random_nodes(rng, system, 0);
+  if nodes.is_empty() {
+    nodes = random_nodes(rng, system, 1);
+  }
+  let mut slots = nodes
+    .iter()
+    .enumerate()
+    .flat_map(|(i, node)| (0..node.ports.len()).map(move |j| (i, j)))
+    .collect::<Vec<_>>();
+  rng.shuffle(&mut slots);
+
+  let free = (rng.below(4).min(slots.len()) & !1) | (slots.len() & 1);
+  let mut free_vars = Vec::new();
+  for &(i, j) in &slots[..free] {
+    let var = vars.define(format!("v{}", vars.defs.len()), ());
+    nodes[i].ports[j] = var;
+    free_vars.push(var);
+  }
+  for pair in slots[free..].chunks(2) {
+    let var = vars.define(format!("v{}", vars.defs.len()), ());
+    for &(i, j) in pair {
+      nodes[i].ports[j] = var;
+    }
+  }
+
+  let mut free_vars = free_vars.into_iter();
+  let ports = random_partition(rng, free, 1)
+    .iter()
+    .map(|part| part.map(&mut |()| free_vars.next().unwrap()))
+    .collect();
+  NetDef { name, vars, ports, nodes, constraints: Vec::new(), expect: None, allow: Vec::new() }
+}
+
+/// A partition of `ports` ports, with groups nested up to `depth` deep.
+fn random_partition(rng: &mut Rng, ports: usize, depth: usize) -> Vec<Partition<()>> {
+  partition(ports, rng.below(1 << ports.saturating_sub(1)))
+    .into_iter()
+    .map(|part| match part {
+      Partition::Group(parts) if depth > 0 && rng.below(2) == 0 => {
+        Partition::Group(random_partition(rng, parts.len(), depth - 1))
+      }
+      part => part,
+    })
+    .collect()
+}
+
+/// Up to three nodes with unassigned ports, chosen so that the total number of
+/// ports plus `extra` is even.
+fn random_nodes(rng: &mut Rng, system: &System, extra: usize) -> Vec<Node> {
+  let agents = &system.agents.defs;
+  let node =
+    |agent: Agent| Node { agent, ports: vec![Var(0); agents[agent].value.arity()], span: None };
+  let mut nodes =
+    (0..rng.below(4)).map(|_| node(Agent(rng.below(agents.len())))).collect::<Vec<_>>();
+  let slots = extra + nodes.iter().map(|node| node.ports.len()).sum::<usize>();
+  if slots % 2 == 1 {
+    let odd = agents.keys().filter(|&a| agents[a].value.arity() % 2 == 1).collect::<Vec<_>>();
+    if !odd.is_empty() {
+      nodes.push(node(odd[rng.below(odd.len())]));
+    }
+  }
+  nodes
+}
+
+/// Removes nodes from `item` one at a time for as long as `fails` still holds,
+/// returning a system containing only the reduced item.
+#[cfg(test)]
+fn shrink(system: &System, item: Item, fails: impl Fn(&System, Item) -> bool) -> System {
+  let mut system = match item {
+    Item::Rule(i) => {
+      System { rules: vec![system.rules[i].clone()], nets: Vec::new(), ..system.clone() }
+    }
+    Item::Net(i) => {
+      System { rules: Vec::new(), nets: vec![system.nets[i].clone()], ..system.clone() }
+    }
+  };
+  let item = match item {
+    Item::Rule(_) => Item::Rule(0),
+    Item::Net(_) => Item::Net(0),
+  };
+  let mut i = 0;
+  while i < nodes(&mut system, item).len() {
+    let mut smaller = system.clone();
+    nodes(&mut smaller, item).remove(i);
+    if fails(&smaller, item) {
+      system = smaller;
+    } else {
+      i += 1;
+    }
+  }
+  system
+}
+
+#[cfg(test)]
+fn nodes(system: &mut System, item: Item) -> &mut Vec<Node> {
+  match item {
+    Item::Rule(i) => &mut system.rules[i].result,
+    Item::Net(i) => &mut system.nets[i].nodes,
+  }
+}
+
+/// The seeds each property is checked for by default, few enough to keep the
+/// tests quick without optimizations.
+#[cfg(test)]
+const QUICK_SEEDS: Range<u64> = 0..100;
+
+/// The seeds every property is checked for by `properties_hold_at_length`,
+/// which is ignored by default; run it with
+/// `cargo test --release -- --ignored`.
+#[cfg(test)]
+const LONG_SEEDS: Range<u64> = 0..5000;
+
+/// Checks that `fails` holds for no item of the systems generated from
+/// `seeds`.
+#[cfg(test)]
+fn check_property(name: &str, seeds: Range<u64>, fails: impl Fn(&System, Item) -> bool) {
+  for seed in seeds {
+    let system = system(&mut Rng::new(seed));
+    for item in Item::all(&system) {
+      if fails(&system, item) {
+        let system = shrink(&system, item, &fails);
+        panic!("{name} fails for seed {seed}, reduced to:\n{system}");
+      }
+    }
+  }
+}
+
+#[cfg(test)]
+fn incomplete(system: &System, item: Item) -> bool {
+  let mut diagram = item.diagram(system);
+  diagram.complete();
+  !diagram.is_complete()
+}
+
+#[test]
+fn complete_is_complete() {
+  check_property("complete_is_complete", QUICK_SEEDS, incomplete);
+}
+
+#[cfg(test)]
+fn closure_changes(system: &System, item: Item) -> bool {
+  let mut diagram = item.diagram(system);
+  diagram.complete();
+  let closed = diagram.graph.clone();
+  diagram.complete();
+  diagram.graph != closed
+}
+
+#[test]
+fn closure_is_idempotent() {
+  check_property("closure_is_idempotent", QUICK_SEEDS, closure_changes);
+}
+
+/// The same system, written differently: the nodes of `item` and the parts of
+/// every partition are shuffled, along with the auxiliary ports of every agent
+/// and the numbering of the variables of `item`.
+#[cfg(test)]
+fn reorder(rng: &mut Rng, system: &System, item: Item) -> System {
+  let mut system = system.clone();
+  rng.shuffle(nodes(&mut system, item));
+
+  for (agent, def) in system.agents.defs.iter_mut() {
+    let def = &mut def.value;
+    let mut i = 0;
+    let mut numbered =
+      def.partition().iter().map(|x| x.map(&mut |()| (i += 1, i).1)).collect::<Vec<_>>();
+    shuffle_partition(rng, &mut numbered);
+    let order = [0]
+      .into_iter()
+      .chain(numbered.iter().flat_map(Partition::ports).copied())
+      .collect::<Vec<_>>();
+    let names = def.names().into_iter().map(|x| x.map(str::to_owned)).collect::<Vec<_>>();
+    def.auxiliary = numbered.iter().map(|x| x.map(&mut |&i| names[i - 1].clone())).collect();
+    let position = |i: usize| order.iter().position(|&j| j == i).unwrap();
+    for axiom in &mut def.axioms {
+      (axiom.a, axiom.b) = (position(axiom.a), position(axiom.b));
+    }
+    let bodies = system
+      .rules
+      .iter_mut()
+      .flat_map(|rule| [&mut rule.a, &mut rule.b].into_iter().chain(&mut rule.result))
+      .chain(system.nets.iter_mut().flat_map(|net| &mut net.nodes));
+    for node in bodies.filter(|node| node.agent == agent) {
+      node.ports = order.iter().map(|&i| node.ports[i]).collect();
+    }
+  }
+
+  let vars = item.vars(&system);
+  let mut old = vars.defs.keys().collect::<Vec<_>>();
+  rng.shuffle(&mut old);
+  let mut new = vec![Var(0); old.len()];
+  for (i, &var) in old.iter().enumerate() {
+    new[var.0] = Var(i);
+  }
+  let defs = old.iter().map(|&var| vars.defs[var].clone()).collect::<Vec<_>>();
+  let rename = |var: &mut Var| *var = new[var.0];
+  let (nodes, constraints): (Vec<&mut Node>, _) = match item {
+    Item::Rule(i) => {
+      let rule = &mut system.rules[i];
+      rule.vars.defs = defs.into();
+      (
+        [&mut rule.a, &mut rule.b].into_iter().chain(&mut rule.result).collect(),
+        &mut rule.constraints,
+      )
+    }
+    Item::Net(i) => {
+      let net = &mut system.nets[i];
+      net.vars.defs = defs.into();
+      net.ports = net.ports.iter().map(|x| x.map(&mut |var| new[var.0])).collect();
+      shuffle_partition(rng, &mut net.ports);
+      (net.nodes.iter_mut().collect(), &mut net.constraints)
+    }
+  };
+  for node in nodes {
+    node.ports.iter_mut().for_each(rename);
+  }
+  for constraint in constraints {
+    rename(&mut constraint.a);
+    rename(&mut constraint.b);
+  }
+  system
+}
+
+/// Shuffles the parts of `parts` and of every group nested in them.
+#[cfg(test)]
+fn shuffle_partition<T>(rng: &mut Rng, parts: &mut [Partition<T>]) {
+  rng.shuffle(parts);
+  for part in parts {
+    if let Partition::Group(parts) = part {
+      shuffle_partition(rng, parts);
+    }
+  }
+}
+
+/// The verdict of `diagram` with its edges inserted again in a random order.
+#[cfg(test)]
+fn shuffled_verdict(rng: &mut Rng, diagram: Diagram) -> Verdict {
+  let mut edges = diagram
+    .graph
+    .nodes
+    .iter()
+    .flat_map(|(a, x)| x.edges.iter().map(move |(&b, &arrow)| (a, b, arrow)))
+    .collect::<Vec<_>>();
+  rng.shuffle(&mut edges);
+  let mut graph = Graph::default();
+  for (a, b, arrow) in edges {
+    graph.insert(a, b, arrow);
+  }
+  Verdict::of(&mut Diagram { graph, ..diagram })
+}
+
+#[cfg(test)]
+fn verdict_depends_on_order(system: &System, item: Item) -> bool {
+  let verdict = item.check(system);
+  (0..2).any(|seed| {
+    let mut rng = Rng::new(seed);
+    let system = reorder(&mut rng, system, item);
+    shuffled_verdict(&mut rng, item.diagram(&system)) != verdict
+  })
+}
+
+#[test]
+fn verdict_is_independent_of_order() {
+  check_property("verdict_is_independent_of_order", QUICK_SEEDS, verdict_depends_on_order);
+}
+
+/// Joining with a contradiction derives nothing, so once an edge is
+/// contradictory the rest of the graph depends on the order edges were
+/// processed in; only consistent diagrams must agree exactly.
+#[cfg(test)]
+fn closure_disagrees_with_naive_closure(system: &System, item: Item) -> bool {
+  let mut diagram = item.diagram(system);
+  let mut naive = diagram.graph.clone();
+  naive.naive_closure(|a, x, _, y, c| if a != c { Arrow::join(x, y) } else { None });
+  diagram.complete();
+  let naive_is_contradictory = naive.nodes.values().any(|x| x.edges.values().any(|&x| x.0 == 0));
+  if diagram.is_contradictory() {
+    !naive_is_contradictory
+  } else {
+    diagram.graph != naive
+  }
+}
+
+#[test]
+fn closure_agrees_with_naive_closure() {
+  check_property(
+    "closure_agrees_with_naive_closure",
+    QUICK_SEEDS,
+    closure_disagrees_with_naive_closure,
+  );
+}
+
+/// Counts the uses of each variable of every item, including the free ports
+/// of nets, as `system` promises.
+#[cfg(test)]
+fn misused_vars(system: &System, item: Item) -> bool {
+  let mut uses = vec![0; item.vars(system).defs.len()];
+  let free = match item {
+    Item::Rule(_) => Vec::new(),
+    Item::Net(i) => system.nets[i].ports.iter().flat_map(Partition::ports).copied().collect(),
+  };
+  let nodes = item.nodes(system);
+  for var in nodes.iter().flat_map(|node| &node.ports).chain(&free) {
+    uses[var.0] += 1;
+  }
+  uses.iter().any(|&n| n != 2) || !item.arity_errors(system).is_empty()
+}
+
+#[test]
+fn systems_are_well_formed() {
+  check_property("systems_are_well_formed", QUICK_SEEDS, misused_vars);
+}
+
+#[test]
+fn generates_nested_partitions() {
+  let nested = |part: &Partition<_>| match part {
+    Partition::Group(parts) => parts.iter().any(|x| matches!(x, Partition::Group(_))),
+    Partition::Port(_) => false,
+  };
+  let systems = QUICK_SEEDS.map(|seed| system(&mut Rng::new(seed))).collect::<Vec<_>>();
+  let mut agents = systems.iter().flat_map(|system| system.agents.defs.values());
+  assert!(agents.any(|agent| agent.value.auxiliary.iter().any(nested)));
+}
+
+#[test]
+#[ignore]
+fn properties_hold_at_length() {
+  check_property("systems_are_well_formed", LONG_SEEDS, misused_vars);
+  check_property("complete_is_complete", LONG_SEEDS, incomplete);
+  check_property("closure_is_idempotent", LONG_SEEDS, closure_changes);
+  check_property("verdict_is_independent_of_order", LONG_SEEDS, verdict_depends_on_order);
+  check_property(
+    "closure_agrees_with_naive_closure",
+    LONG_SEEDS,
+    closure_disagrees_with_naive_closure,
+  );
+}
@@ -0,0 +1,201 @@
+use crate::{
+  arrow::Arrow,
+  ast::{Partition, RuleDef, System, Verdict},
+  check,
+  diagram::Diagram,
+  interface::relation,
+};
+
+/// A pair of free ports of a rule whose relation in the result rules out
+/// orderings that the active pair allows.
+#[derive(Debug, Clone, Copy)]
+pub struct Strengthening {
+  pub a: usize,
+  pub b: usize,
+  pub lhs: Arrow,
+  pub rhs: Arrow,
+}
+
+/// Which side of a rule is contradictory on its own, so that there is nothing
+/// to compare.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Contradiction {
+  /// The axioms of the two agents cannot hold together once their principal
+  /// ports are connected.
+  ActivePair,
+  Result,
+}
+
+/// The active pair of a rule, with each of its auxiliary ports connected to a
+/// free port of its own, narrowed by the rule's assumptions between them.
+pub fn lhs_diagram(system: &System, rule: &RuleDef) -> Diagram {
+  let mut diagram = Diagram::default();
+
+  let a = diagram.add_agent(&rule.a, &system.agents.defs[rule.a.agent].value);
+  let b = diagram.add_agent(&rule.b, &system.agents.defs[rule.b.agent].value);
+  diagram.link(a[0], b[0]);
+
+  let aux = a[1..].iter().chain(&b[1..]).copied().collect::<Vec<_>>();
+  let ports = diagram.add_free_ports(&aux.iter().map(Partition::Port).collect::<Vec<_>>());
+  for (&port, aux) in ports.iter().zip(aux) {
+    diagram.link(port, aux);
+  }
+
+  let vars = rule.a.auxiliary().iter().chain(rule.b.auxiliary()).copied();
+  diagram.endpoints.extend(vars.zip(ports));
+  let endpoints = &diagram.endpoints;
+  let constraints = rule
+    .constraints
+    .iter()
+    .filter(|x| endpoints.contains_key(&x.a) && endpoints.contains_key(&x.b))
+    .copied()
+    .collect::<Vec<_>>();
+  check::assume(&mut diagram, &constraints);
+
+  diagram
+}
+
+/// The result of a rule, with no assumptions about its free ports beyond
+/// those the rule states.
+pub fn rhs_diagram(system: &System, rule: &RuleDef) -> Diagram {
+  let mut diagram = Diagram::default();
+
+  let ports = rule.a.auxiliary().iter().chain(rule.b.auxiliary()).copied().collect::<Vec<_>>();
+  diagram.insert_free_ports(
+    ports.iter().copied(),
+    &ports.iter().map(Partition::Port).collect::<Vec<_>>(),
+  );
+
+  for node in &rule.result {
+    let agent = &system.agents.defs[node.agent].value;
+    diagram.insert_agent(node, agent);
+  }
+  check::assume(&mut diagram, &rule.constraints);
+
+  diagram
+}
+
+/// Checks that the relation the result of a rule derives between its free
+/// ports is entailed by the relation the active pair imposes on them, so that
+/// any context in which the active pair is consistent stays consistent after
+/// the rule fires.
+pub fn strengthenings(
+  system: &System,
+  rule: &RuleDef,
+) -> Result<Vec<Strengthening>, Contradiction> {
+  let mut lhs = lhs_diagram(system, rule);
+  let mut rhs = rhs_diagram(system, rule);
+  if Verdict::of(&mut lhs) == Verdict::NonSimple {
+    return Err(Contradiction::ActivePair);
+  }
+  if Verdict::of(&mut rhs) == Verdict::NonSimple {
+    return Err(Contradiction::Result);
+  }
+
+  let lhs = relation(&lhs);
+  let rhs = relation(&rhs);
+  let mut strengthenings = Vec::new();
+  for a in 0..lhs.len() {
+    for b in a + 1..lhs.len() {
+      if lhs[a][b].0 & !rhs[a][b].0 != 0 {
+        strengthenings.push(Strengthening { a, b, lhs: lhs[a][b], rhs: rhs[a][b] });
+      }
+    }
+  }
+  Ok(strengthenings)
+}
+
+#[test]
+fn strengthenings_of_rules() {
+  use crate::parser::SimplicityParser;
+
+  let system = SimplicityParser::parse(
+    "
+    agent C(*, *, *)
+    agent D(*, {*, *})
+    agent E(*)
+    rule C(_, a, b) E(_) {
+      D(a, b, x)
+      E(x)
+    }
+    rule D(_, a, b) E(_) {
+      C(a, b, x)
+      E(x)
+    }
+    rule C(_, a, b) C(_, c, d) {
+      C(a, x, y)
+      C(b, z, w)
+      C(c, x, z)
+      C(d, y, w)
+    }
+    ",
+  )
+  .unwrap();
+  let strengthenings = |i: usize| {
+    strengthenings(&system, &system.rules[i])
+      .map(|x| x.iter().map(|x| (x.a, x.b, x.lhs, x.rhs)).collect::<Vec<_>>())
+  };
+
+  // Grouping ports that were apart only narrows what the result allows.
+  assert_eq!(strengthenings(0), Ok(vec![]));
+  // Splitting ports that were grouped relates them in a way the active pair
+  // did not.
+  assert_eq!(strengthenings(1), Ok(vec![(0, 1, Arrow(0b11111), Arrow(0b00100))]));
+  // The result is contradictory on its own.
+  assert_eq!(strengthenings(2), Err(Contradiction::Result));
+}
+
+#[test]
+fn assumptions_narrow_both_sides() {
+  use crate::parser::SimplicityParser;
+
+  let system = SimplicityParser::parse(
+    "
+    agent C(*, *, *)
+    agent D(*, {*, *})
+    agent E(*)
+    rule D(_, a, b) E(_) {
+      C(a, b, x)
+      E(x)
+      assume a --*-- b
+    }
+    rule D(_, a, b) E(_) {
+      C(a, b, x)
+      E(x)
+      assume a --*-- x
+    }
+    rule D(_, a, b) E(_) {
+      C(a, b, x)
+      E(x)
+      assume a <<--- b
+    }
+    ",
+  )
+  .unwrap();
+  let strengthenings = |i: usize| {
+    strengthenings(&system, &system.rules[i])
+      .map(|x| x.iter().map(|x| (x.a, x.b, x.lhs, x.rhs)).collect::<Vec<_>>())
+  };
+
+  // Once the ports are assumed to overlap, splitting them is no strengthening.
+  assert_eq!(strengthenings(0), Ok(vec![]));
+  // An assumption about the result alone leaves the active pair as it was.
+  assert_eq!(strengthenings(1), Ok(vec![(0, 1, Arrow(0b11111), Arrow(0b00100))]));
+  // The result cannot meet an assumption the active pair can.
+  assert_eq!(strengthenings(2), Err(Contradiction::Result));
+}
+
+#[test]
+fn reports_contradictory_active_pairs() {
+  use crate::{
+    ast::{Agent, Axiom},
+    parser::SimplicityParser,
+  };
+
+  let mut system =
+    SimplicityParser::parse("agent A(*, x) agent B(*) rule A(_, x) B(_) { B(x) }").unwrap();
+  assert_eq!(strengthenings(&system, &system.rules[0]).unwrap().len(), 0);
+  // The parser rejects axioms like this one, which contradicts the partition.
+  system.agents.defs[Agent(0)].value.axioms.push(Axiom { a: 0, arrow: Arrow(0b00100), b: 1 });
+  assert!(matches!(strengthenings(&system, &system.rules[0]), Err(Contradiction::ActivePair)));
+}
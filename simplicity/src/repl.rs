@@ -0,0 +1,268 @@
+use std::io::{self, BufRead, Write};
+
+use util::lexer::TokenSet;
+
+use crate::{
+  ast::{NetDef, Node, Partition, System, Var, Verdict},
+  check::Item,
+  parser::{ParseError, SimplicityParser},
+};
+
+const HELP: &str = "\
+agent Name(*, ...)   define an agent
+Name(a, b, ...)      add a node to the scratch net
+ports (a, {b, c})    set the free ports of the scratch net
+arrow a b            show the derived relation between two variables
+undo                 remove the last node
+show                 print the scratch net
+verdict              check whether the scratch net is simple
+clear                empty the scratch net
+quit                 leave the repl";
+
+/// An interactive session that builds a scratch net one node at a time on
+/// top of the agents of a system.
+///
+/// The session keeps the source of everything entered, and parses it afresh
+/// for each command, since the parser borrows from the source.
+pub struct Repl {
+  /// The loaded file, followed by the agents defined since, one per line.
+  defs: String,
+  /// The free ports of the scratch net, as written after `ports`.
+  ports: Option<String>,
+  /// The nodes of the scratch net, as written.
+  nodes: Vec<String>,
+}
+
+/// The scratch net of a [`Repl`], parsed from its source.
+struct Scratch<'src> {
+  parser: SimplicityParser<'src>,
+  ports: Vec<Partition<Var>>,
+  nodes: Vec<Node>,
+}
+
+impl Repl {
+  pub fn new(src: Option<&str>) -> Result<Self, ParseError<'_>> {
+    let defs = src.unwrap_or("");
+    SimplicityParser::parse(defs)?;
+    Ok(Repl { defs: defs.to_owned(), ports: None, nodes: Vec::new() })
+  }
+
+  pub fn run(&mut self) -> io::Result<()> {
+    let mut stdin = io::stdin().lock();
+    loop {
+      print!("> ");
+      io::stdout().flush()?;
+      let mut line = String::new();
+      if stdin.read_line(&mut line)? == 0 {
+        return Ok(());
+      }
+      let line = line.trim();
+      if line == "quit" {
+        return Ok(());
+      }
+      if !line.is_empty() {
+        print!("{}", self.command(line));
+      }
+    }
+  }
+
+  /// Runs the command on `line`, returning what it prints.
+  fn command(&mut self, line: &str) -> String {
+    let (command, rest) = line.split_once(' ').unwrap_or((line, ""));
+    let nodes = self.nodes.join("\n");
+    let ports = self.ports.as_deref();
+    let result = match command {
+      "help" => Ok(format!("{HELP}\n")),
+      "undo" => {
+        self.nodes.pop();
+        Ok(String::new())
+      }
+      "clear" => {
+        self.ports = None;
+        self.nodes.clear();
+        Ok(String::new())
+      }
+      "show" => Scratch::parse(&self.defs, ports, &nodes).map(|x| x.show()).map_err(show_error),
+      "verdict" => Scratch::parse(&self.defs, ports, &nodes)
+        .map(|x| format!("{}\n", x.check()))
+        .map_err(show_error),
+      "arrow" => match rest.split_whitespace().collect::<Vec<_>>()[..] {
+        [a, b] => {
+          Scratch::parse(&self.defs, ports, &nodes).map(|x| x.arrow(a, b)).map_err(show_error)
+        }
+        _ => Ok("usage: arrow <var> <var>\n".to_owned()),
+      },
+      "ports" => {
+        Scratch::parse(&self.defs, Some(rest), &nodes).map(|_| ()).map_err(show_error).map(|()| {
+          self.ports = Some(rest.to_owned());
+          String::new()
+        })
+      }
+      "agent" => {
+        let defs = format!("{}\n{line}", self.defs);
+        Scratch::parse(&defs, ports, &nodes).map(|_| ()).map_err(show_error).map(|()| {
+          self.defs = defs.clone();
+          String::new()
+        })
+      }
+      _ => {
+        let scratch = Scratch::parse(&self.defs, ports, &nodes);
+        scratch.and_then(|mut x| x.add_node(line)).map_err(show_error).map(|error| match error {
+          Some(error) => format!("error: {error}\n"),
+          None => {
+            self.nodes.push(line.to_owned());
+            String::new()
+          }
+        })
+      }
+    };
+    result.unwrap_or_else(|error| error)
+  }
+}
+
+fn show_error(error: ParseError) -> String {
+  format!("error: {error:?}\n")
+}
+
+impl<'src> Scratch<'src> {
+  /// Parses the agents of `defs`, then the free ports and nodes of the scratch
+  /// net. Each node is on its own line of `nodes`, so that the line of its
+  /// span is its position in the net.
+  fn parse(
+    defs: &'src str,
+    ports: Option<&'src str>,
+    nodes: &'src str,
+  ) -> Result<Self, ParseError<'src>> {
+    let mut parser = SimplicityParser::new(defs);
+    parser.load(defs)?;
+    parser.vars.finish();
+    let mut scratch = Scratch { parser, ports: Vec::new(), nodes: Vec::new() };
+    if let Some(ports) = ports {
+      scratch.parser.restart(ports)?;
+      scratch.ports = scratch.parser.parse_ports()?;
+      scratch.expect_end()?;
+    }
+    scratch.parser.restart(nodes)?;
+    while scratch.parser.state.token.is_some() {
+      scratch.nodes.extend(scratch.parser.parse_node()?);
+    }
+    scratch.parser.report_undefined()?;
+    Ok(scratch)
+  }
+
+  /// Parses `line` as one more node, returning why it cannot be added if its
+  /// agent has a different number of ports.
+  fn add_node(&mut self, line: &'src str) -> Result<Option<String>, ParseError<'src>> {
+    self.parser.restart(line)?;
+    let node = self.parser.parse_node()?;
+    self.expect_end()?;
+    let Some(node) = node else { return Err(self.parser.undefined_agents()) };
+    self.nodes.push(node);
+    // The nodes before it were all checked when they were added.
+    if let Some(error) = Item::Net(0).arity_errors(&self.system()).pop() {
+      self.nodes.pop();
+      return Ok(Some(error));
+    }
+    Ok(None)
+  }
+
+  fn expect_end(&mut self) -> Result<(), ParseError<'src>> {
+    match self.parser.state.token {
+      None => Ok(()),
+      Some(_) => Err(ParseError::UnexpectedToken {
+        expected: TokenSet::default(),
+        found: self.parser.state.lexer.slice(),
+      }),
+    }
+  }
+
+  /// The loaded agents, with the scratch net as the only net.
+  fn system(&self) -> System {
+    let net = NetDef {
+      name: "scratch".into(),
+      vars: self.parser.vars.scope.clone(),
+      ports: self.ports.clone(),
+      nodes: self.nodes.clone(),
+      constraints: Vec::new(),
+      expect: None,
+      allow: Vec::new(),
+    };
+    System { agents: self.parser.agents.scope.clone(), rules: Vec::new(), nets: vec![net] }
+  }
+
+  fn check(&self) -> Verdict {
+    Item::Net(0).check(&self.system())
+  }
+
+  fn arrow(&self, a: &str, b: &str) -> String {
+    let system = self.system();
+    let mut diagram = Item::Net(0).diagram(&system);
+    let verdict = Verdict::of(&mut diagram);
+    let mut nodes = [a, b].into_iter().map(|name| {
+      let var = self.parser.vars.lookup.get(name)?;
+      diagram.endpoints.get(var).copied()
+    });
+    let (Some(a_node), Some(b_node)) = (nodes.next().unwrap(), nodes.next().unwrap()) else {
+      return "both variables must appear in the scratch net\n".to_owned();
+    };
+    let mut out = match diagram.graph.get_edge(a_node, b_node) {
+      Some(arrow) => format!("{a} {arrow:?} {b}\n"),
+      None => format!("{a} and {b} are unconstrained\n"),
+    };
+    if verdict == Verdict::NonSimple {
+      out += "note: the scratch net is non-simple\n";
+    }
+    out
+  }
+
+  fn show(&self) -> String {
+    let vars = &self.parser.vars.scope;
+    let agents = &self.parser.agents.scope;
+    let mut out = String::new();
+    if !self.ports.is_empty() {
+      let ports = self.ports.iter().map(|part| part.show(&|&v| vars.defs[v].name.clone()));
+      out += &format!("ports ({})\n", ports.collect::<Vec<_>>().join(", "));
+    }
+    for node in &self.nodes {
+      let ports = node.ports.iter().map(|&v| &*vars.defs[v].name).collect::<Vec<_>>();
+      out += &format!("{}({})\n", agents.defs[node.agent].name, ports.join(", "));
+    }
+    out
+  }
+}
+
+#[test]
+fn builds_a_scratch_net() {
+  let mut repl = Repl::new(Some("agent Con(*, {*, *})\nagent Era(*)")).unwrap();
+  let mut run = |line| repl.command(line);
+  assert_eq!(run("Con(r, a, b)"), "");
+  assert_eq!(run("Era(a)"), "");
+  assert_eq!(run("Era(a, b)"), "error: agent `Era(*)` has 1 ports, but 2 were given\n");
+  assert!(run("Dup(b)").starts_with("error: UndefinedAgents"));
+  assert!(run("Era(b) Era(b)").starts_with("error: UnexpectedToken"));
+  assert_eq!(run("agent Dup(*)"), "");
+  assert!(run("agent Era(*)").starts_with("error: DuplicateAgentDef"));
+  assert_eq!(run("Dup(b)"), "");
+  assert_eq!(run("ports (r)"), "");
+  assert_eq!(run("show"), "ports (r)\nCon(r, a, b)\nEra(a)\nDup(b)\n");
+  assert_eq!(run("verdict"), "simple\n");
+  assert_eq!(run("arrow r a"), "r --*-- a\n");
+  assert_eq!(run("arrow a b"), "a and b are unconstrained\n");
+  assert_eq!(run("arrow a c"), "both variables must appear in the scratch net\n");
+  assert_eq!(run("undo"), "");
+  assert_eq!(run("show"), "ports (r)\nCon(r, a, b)\nEra(a)\n");
+  assert_eq!(run("clear"), "");
+  assert_eq!(run("show"), "");
+}
+
+#[test]
+fn numbers_scratch_nodes_by_line() {
+  let mut repl = Repl::new(None).unwrap();
+  for line in ["agent A(*, *)", "A(a, b)", "A(b, c)", "A(c, a)"] {
+    assert_eq!(repl.command(line), "");
+  }
+  let nodes = repl.nodes.join("\n");
+  let scratch = Scratch::parse(&repl.defs, None, &nodes).unwrap();
+  let lines = scratch.nodes.iter().map(|node| node.span.unwrap().line).collect::<Vec<_>>();
+  assert_eq!(lines, [1, 2, 3]);
+}
@@ -31,6 +31,8 @@ pub struct ScopeBuilder<'src, I: Idx, T> {
 }
 
 impl<'src, I: Idx, T> ScopeBuilder<'src, I, T> {
+  /// Defines `name`, failing if it is already defined.
+  #[allow(clippy::result_unit_err)]
   pub fn define(&mut self, name: &'src str, value: T) -> Result<I, ()> {
     let index = self.scope.defs.next_index();
     if self.lookup.insert(name, index).is_some() {
@@ -0,0 +1,151 @@
+use crate::{
+  ast::{Agent, AgentDef, Partition, System, Verdict},
+  check::Item,
+};
+
+/// Every way of grouping `ports` consecutive auxiliary ports, without nested
+/// groups.
+pub fn partitions(ports: usize) -> Vec<Vec<Partition<()>>> {
+  (0..1usize << ports.saturating_sub(1)).map(|breaks| partition(ports, breaks)).collect()
+}
+
+/// The grouping of `ports` consecutive auxiliary ports that starts a new group
+/// after port `i` whenever bit `i` of `breaks` is set.
+pub fn partition(ports: usize, breaks: usize) -> Vec<Partition<()>> {
+  if ports == 0 {
+    return vec![];
+  }
+  let mut groups = vec![1];
+  for i in 1..ports {
+    if breaks & (1 << (i - 1)) != 0 {
+      groups.push(0);
+    }
+    *groups.last_mut().unwrap() += 1;
+  }
+  groups
+    .into_iter()
+    .map(|n| match n {
+      1 => Partition::Port(()),
+      _ => Partition::Group(vec![Partition::Port(()); n]),
+    })
+    .collect()
+}
+
+/// Finds the smallest sets of partition changes to the agents involved in
+/// `system.rules[rule]` that make it simple, while keeping every other rule
+/// and net that is currently simple, simple.
+pub fn suggest(system: &System, rule: usize) -> Vec<Vec<(Agent, AgentDef)>> {
+  let mut involved = Vec::new();
+  for agent in Item::Rule(rule).agents(system) {
+    if !involved.contains(&agent) {
+      involved.push(agent);
+    }
+  }
+
+  let passing = Item::all(system)
+    .filter(|&item| item != Item::Rule(rule) && item.check(system) == Verdict::Simple)
+    .collect();
+
+  let mut search = Search {
+    system: system.clone(),
+    rule,
+    passing,
+    involved,
+    changes: Vec::new(),
+    found: Vec::new(),
+  };
+  for size in 1..=search.involved.len() {
+    search.search(0, size);
+    if !search.found.is_empty() {
+      break;
+    }
+  }
+  search.found
+}
+
+struct Search {
+  system: System,
+  rule: usize,
+  passing: Vec<Item>,
+  involved: Vec<Agent>,
+  changes: Vec<(Agent, AgentDef)>,
+  found: Vec<Vec<(Agent, AgentDef)>>,
+}
+
+impl Search {
+  fn search(&mut self, start: usize, size: usize) {
+    if size == 0 {
+      if self.is_solution() {
+        self.found.push(self.changes.clone());
+      }
+      return;
+    }
+    for i in start..self.involved.len() {
+      let agent = self.involved[i];
+      let original = self.system.agents.defs[agent].value.clone();
+      let ports = original.arity() - 1;
+      for partition in partitions(ports) {
+        if partition == original.partition() {
+          continue;
+        }
+        let def = original.regroup(&partition);
+        self.system.agents.defs[agent].value = def.clone();
+        self.changes.push((agent, def));
+        self.search(i + 1, size - 1);
+        self.changes.pop();
+      }
+      self.system.agents.defs[agent].value = original;
+    }
+  }
+
+  fn is_solution(&self) -> bool {
+    let system = &self.system;
+    Item::Rule(self.rule).check(system) == Verdict::Simple
+      && self.passing.iter().all(|item| item.check(system) == Verdict::Simple)
+  }
+}
+
+#[test]
+fn partitions_of_three_ports() {
+  let (port, pair) = (Partition::Port(()), Partition::Group(vec![Partition::Port(()); 2]));
+  assert_eq!(partition(3, 0b00), vec![Partition::Group(vec![Partition::Port(()); 3])]);
+  assert_eq!(partition(3, 0b01), vec![port.clone(), pair.clone()]);
+  assert_eq!(partition(3, 0b10), vec![pair, port.clone()]);
+  assert_eq!(partition(3, 0b11), vec![port; 3]);
+  assert_eq!(partitions(3).len(), 4);
+  assert_eq!(partitions(0), vec![vec![]]);
+}
+
+#[test]
+fn suggestions_keep_other_items_simple() {
+  use crate::parser::SimplicityParser;
+
+  let rule = "
+    agent Con(*, {*, *})
+    agent Dup(*, {*, *})
+    rule Con(_, a, b) Dup(_, c, d) {
+      Dup(a, x, y)
+      Dup(b, z, w)
+      Con(c, x, z)
+      Con(d, y, w)
+    }
+  ";
+  let show = |system: &System, suggestions: Vec<Vec<(Agent, AgentDef)>>| {
+    let show = |(agent, def): &(Agent, AgentDef)| def.show(&system.agents.defs[*agent].name);
+    suggestions.iter().map(|x| x.iter().map(show).collect::<Vec<_>>()).collect::<Vec<_>>()
+  };
+
+  let system = SimplicityParser::parse(rule).unwrap();
+  assert_eq!(show(&system, suggest(&system, 0)), [["Con(*, *, *)"], ["Dup(*, *, *)"]]);
+
+  // Ungrouping the ports of `Con` would make this net non-simple.
+  let net = "
+    net n(r) {
+      Con(r, a, b)
+      Con(a, x, y)
+      Dup(b, x, y)
+    }
+  ";
+  let system = SimplicityParser::parse(&format!("{rule}{net}")).unwrap();
+  assert_eq!(show(&system, suggest(&system, 0)), [["Dup(*, *, *)"]]);
+}
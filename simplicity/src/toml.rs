@@ -0,0 +1,248 @@
+/// The subset of TOML used by `simplicity.toml`: tables, keys (bare or
+/// quoted), strings, integers, booleans and arrays, which may span lines.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Value {
+  String(String),
+  Integer(i64),
+  Bool(bool),
+  Array(Vec<Value>),
+}
+
+#[derive(Debug, Default)]
+pub struct Document {
+  /// Each table with its entries, starting with the unnamed root table.
+  pub tables: Vec<(String, Vec<(String, Value)>)>,
+}
+
+impl Value {
+  pub fn as_str(&self) -> Option<&str> {
+    match self {
+      Value::String(s) => Some(s),
+      _ => None,
+    }
+  }
+
+  /// The strings of an array of strings.
+  pub fn as_strings(&self) -> Option<Vec<&str>> {
+    match self {
+      Value::Array(items) => items.iter().map(Value::as_str).collect(),
+      _ => None,
+    }
+  }
+}
+
+impl Document {
+  pub fn table(&self, name: &str) -> &[(String, Value)] {
+    self.tables.iter().find(|(x, _)| x == name).map(|(_, entries)| &entries[..]).unwrap_or(&[])
+  }
+
+  pub fn get(&self, table: &str, key: &str) -> Option<&Value> {
+    self.table(table).iter().find(|(x, _)| x == key).map(|(_, value)| value)
+  }
+
+  pub fn parse(src: &str) -> Result<Document, String> {
+    let mut parser = TomlParser { src: src.as_bytes(), i: 0, line: 1 };
+    let mut document = Document { tables: vec![(String::new(), Vec::new())] };
+    loop {
+      parser.skip_trivia(true);
+      match parser.peek() {
+        None => return Ok(document),
+        Some(b'[') => {
+          parser.i += 1;
+          parser.skip_trivia(false);
+          let name = parser.parse_key()?;
+          parser.skip_trivia(false);
+          parser.expect(b']')?;
+          document.tables.push((name, Vec::new()));
+        }
+        Some(_) => {
+          let key = parser.parse_key()?;
+          parser.skip_trivia(false);
+          parser.expect(b'=')?;
+          let value = parser.parse_value()?;
+          document.tables.last_mut().unwrap().1.push((key, value));
+        }
+      }
+      parser.skip_trivia(false);
+      if parser.peek().is_some_and(|c| c != b'\n') {
+        return Err(parser.error("expected a new line"));
+      }
+    }
+  }
+}
+
+struct TomlParser<'a> {
+  src: &'a [u8],
+  i: usize,
+  line: usize,
+}
+
+impl TomlParser<'_> {
+  fn peek(&self) -> Option<u8> {
+    self.src.get(self.i).copied()
+  }
+
+  fn error(&self, message: &str) -> String {
+    format!("line {}: {message}", self.line)
+  }
+
+  fn expect(&mut self, c: u8) -> Result<(), String> {
+    if self.peek() == Some(c) {
+      self.i += 1;
+      Ok(())
+    } else {
+      Err(self.error(&format!("expected `{}`", c as char)))
+    }
+  }
+
+  /// Skips spaces and comments, and new lines if `newlines` is set.
+  fn skip_trivia(&mut self, newlines: bool) {
+    while let Some(c) = self.peek() {
+      match c {
+        b' ' | b'\t' | b'\r' => self.i += 1,
+        b'\n' if newlines => {
+          self.i += 1;
+          self.line += 1;
+        }
+        b'#' => {
+          while self.peek().is_some_and(|c| c != b'\n') {
+            self.i += 1;
+          }
+        }
+        _ => return,
+      }
+    }
+  }
+
+  fn parse_key(&mut self) -> Result<String, String> {
+    if self.peek() == Some(b'"') {
+      return self.parse_string();
+    }
+    let start = self.i;
+    while self.peek().is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-') {
+      self.i += 1;
+    }
+    if start == self.i {
+      return Err(self.error("expected a key"));
+    }
+    Ok(String::from_utf8_lossy(&self.src[start..self.i]).into_owned())
+  }
+
+  fn parse_value(&mut self) -> Result<Value, String> {
+    self.skip_trivia(false);
+    match self.peek() {
+      Some(b'"') => self.parse_string().map(Value::String),
+      Some(b'[') => {
+        self.i += 1;
+        let mut items = Vec::new();
+        loop {
+          self.skip_trivia(true);
+          if self.peek() == Some(b']') {
+            self.i += 1;
+            return Ok(Value::Array(items));
+          }
+          items.push(self.parse_value()?);
+          self.skip_trivia(true);
+          if self.peek() == Some(b',') {
+            self.i += 1;
+          } else {
+            self.skip_trivia(true);
+            self.expect(b']')?;
+            return Ok(Value::Array(items));
+          }
+        }
+      }
+      _ => {
+        let start = self.i;
+        while self
+          .peek()
+          .is_some_and(|c| c.is_ascii_alphanumeric() || c == b'_' || c == b'-' || c == b'+')
+        {
+          self.i += 1;
+        }
+        match &self.src[start..self.i] {
+          b"true" => Ok(Value::Bool(true)),
+          b"false" => Ok(Value::Bool(false)),
+          word => std::str::from_utf8(word)
+            .ok()
+            .and_then(|x| x.replace('_', "").parse().ok())
+            .map(Value::Integer)
+            .ok_or_else(|| self.error("expected a value")),
+        }
+      }
+    }
+  }
+
+  fn parse_string(&mut self) -> Result<String, String> {
+    self.expect(b'"')?;
+    let mut bytes = Vec::new();
+    loop {
+      match self.peek() {
+        None | Some(b'\n') => return Err(self.error("unterminated string")),
+        Some(b'"') => {
+          self.i += 1;
+          return String::from_utf8(bytes).map_err(|_| self.error("invalid utf-8"));
+        }
+        Some(b'\\') => {
+          self.i += 1;
+          let c = match self.peek() {
+            Some(b'n') => b'\n',
+            Some(b't') => b'\t',
+            Some(b'\\') => b'\\',
+            Some(b'"') => b'"',
+            _ => return Err(self.error("unsupported escape")),
+          };
+          self.i += 1;
+          bytes.push(c);
+        }
+        Some(c) => {
+          self.i += 1;
+          bytes.push(c);
+        }
+      }
+    }
+  }
+}
+
+#[test]
+fn parses_documents() {
+  let src = r#"
+    # a comment
+    name = "a \"quoted\" \\ string"  # another
+    count = 1_000
+    flags = [true, false]
+    nested = [
+      ["x", "y"],
+      [],
+    ]
+
+    [table-1]
+    "quoted key" = -3
+  "#;
+  let document = Document::parse(src).unwrap();
+  assert_eq!(document.get("", "name"), Some(&Value::String("a \"quoted\" \\ string".into())));
+  assert_eq!(document.get("", "count"), Some(&Value::Integer(1000)));
+  assert_eq!(
+    document.get("", "flags"),
+    Some(&Value::Array(vec![Value::Bool(true), Value::Bool(false)]))
+  );
+  let nested = document.get("", "nested").unwrap();
+  let Value::Array(nested) = nested else { panic!() };
+  assert_eq!(nested[0].as_strings(), Some(vec!["x", "y"]));
+  assert_eq!(nested[1], Value::Array(Vec::new()));
+  assert_eq!(document.get("table-1", "quoted key"), Some(&Value::Integer(-3)));
+  assert_eq!(document.get("table-1", "name"), None);
+  assert_eq!(document.table("missing"), []);
+}
+
+#[test]
+fn reports_errors_by_line() {
+  let error = |src| Document::parse(src).unwrap_err();
+  assert_eq!(error("a = 1\nb =\n"), "line 2: expected a value");
+  assert_eq!(error("a = 1 b = 2"), "line 1: expected a new line");
+  assert_eq!(error("\n\na = \"x\ny\""), "line 3: unterminated string");
+  assert_eq!(error("a = \"\\x\""), "line 1: unsupported escape");
+  assert_eq!(error("[table\n"), "line 1: expected `]`");
+  assert_eq!(error("= 1"), "line 1: expected a key");
+  assert_eq!(error("a = [1,\n2"), "line 2: expected `]`");
+}
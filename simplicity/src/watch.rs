@@ -0,0 +1,116 @@
+use std::{
+  fs::{metadata, read_to_string},
+  thread::sleep,
+  time::{Duration, SystemTime},
+};
+
+use crate::{check::Item, parser::SimplicityParser};
+
+struct Watched<'a> {
+  path: &'a str,
+  modified: Option<SystemTime>,
+  error: Option<String>,
+  outcomes: Vec<(String, String)>,
+}
+
+/// Polls `paths`, re-checking each file whenever it is modified and printing
+/// only the items whose verdict or diagnostics changed.
+pub fn watch(paths: &[&str]) -> ! {
+  let mut files = paths
+    .iter()
+    .map(|&path| Watched { path, modified: None, error: None, outcomes: Vec::new() })
+    .collect::<Vec<_>>();
+  loop {
+    for file in &mut files {
+      let modified = metadata(file.path).and_then(|x| x.modified()).ok();
+      if modified.is_some() && modified == file.modified {
+        continue;
+      }
+      file.modified = modified;
+      for line in file.update() {
+        println!("{line}");
+      }
+    }
+    sleep(Duration::from_millis(500));
+  }
+}
+
+impl Watched<'_> {
+  /// Re-checks the file, returning the lines to print about what changed.
+  fn update(&mut self) -> Vec<String> {
+    let path = self.path;
+    let mut lines = Vec::new();
+    let outcomes = match outcomes(path) {
+      Ok(outcomes) => outcomes,
+      Err(error) => {
+        if self.error.as_ref() != Some(&error) {
+          lines.push(format!("{path}: {error}"));
+          self.error = Some(error);
+        }
+        return lines;
+      }
+    };
+    if self.error.take().is_some() {
+      lines.push(format!("{path}: ok"));
+    }
+
+    for (name, outcome) in &outcomes {
+      if !self.outcomes.iter().any(|(x, y)| x == name && y == outcome) {
+        lines.push(format!("{path}: {name}: {outcome}"));
+      }
+    }
+    for (name, _) in &self.outcomes {
+      if !outcomes.iter().any(|(x, _)| x == name) {
+        lines.push(format!("{path}: {name}: removed"));
+      }
+    }
+    self.outcomes = outcomes;
+    lines
+  }
+}
+
+/// The verdict or diagnostics of every item in the file at `path`.
+fn outcomes(path: &str) -> Result<Vec<(String, String)>, String> {
+  let src = read_to_string(path).map_err(|error| format!("error: {error}"))?;
+  let system = SimplicityParser::parse(&src).map_err(|error| format!("error: {error:?}"))?;
+  let outcomes = Item::all(&system).map(|item| {
+    let errors = item.arity_errors(&system);
+    let outcome = if errors.is_empty() {
+      let verdict = item.check(&system);
+      match item.expect(&system) {
+        Some(expect) if expect != verdict => format!("{verdict}, expected {expect}"),
+        _ => verdict.to_string(),
+      }
+    } else {
+      format!("error: {}", errors.join("; "))
+    };
+    (item.name(&system), outcome)
+  });
+  Ok(outcomes.collect())
+}
+
+#[test]
+fn reports_only_changes() {
+  let path = std::env::temp_dir().join(format!("simplicity-watch-{}.nets", std::process::id()));
+  let path = path.to_str().unwrap();
+  let mut file = Watched { path, modified: None, error: None, outcomes: Vec::new() };
+  let mut update = |src: &str| {
+    std::fs::write(path, src).unwrap();
+    file.update().into_iter().map(|line| line[path.len() + 2..].to_owned()).collect::<Vec<_>>()
+  };
+  let agents = "agent Con(*, *, *)\nagent Era(*)\n";
+  let erase = "rule Con(_, a, b) Era(_) { Era(a) Era(b) }\n";
+  let commute =
+    "rule Con(_, a, b) Con(_, c, d) { Con(a, x, y) Con(b, z, w) Con(c, x, z) Con(d, y, w) }\n";
+  assert_eq!(update(&format!("{agents}{erase}")), ["rule Con/Era: simple"]);
+  assert_eq!(update(&format!("{agents}{erase}")), [] as [&str; 0]);
+  assert_eq!(update(&format!("{agents}{erase}{commute}")), ["rule Con/Con: non-simple"]);
+  assert_eq!(update(&format!("{agents}{erase}{commute}net n(")).len(), 1);
+  assert_eq!(update(&format!("{agents}{commute}")), ["ok", "rule Con/Era: removed"]);
+  let wrong = "rule Con(_, a, b) Era(_) { Era(a, b) }\n";
+  assert_eq!(
+    update(&format!("{agents}{wrong}{commute}")),
+    ["rule Con/Era: error: agent `Era(*)` has 1 ports, but 2 were given"]
+  );
+  std::fs::remove_file(path).unwrap();
+}
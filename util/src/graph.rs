@@ -6,8 +6,9 @@ use crate::{
 };
 
 mod closure;
+mod naive;
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Graph<I: Idx, E: Edge> {
   pub nodes: IndexVec<I, Node<I, E>>,
 }
@@ -58,6 +59,12 @@ impl<I: Idx, E: Edge> Default for Node<I, E> {
   }
 }
 
+impl<I: Idx, E: Edge> PartialEq for Node<I, E> {
+  fn eq(&self, other: &Self) -> bool {
+    self.edges == other.edges
+  }
+}
+
 impl<I: Idx, E: Edge> Clone for Node<I, E> {
   fn clone(&self) -> Self {
     Self { edges: self.edges.clone(), state: BicycleState::default() }
@@ -8,12 +8,27 @@ use crate::idx::Idx;
 use super::{Edge, Graph};
 
 impl<I: Idx, E: Edge> Graph<I, E> {
+  /// When an edge `a -> c` changes, only paths through `a -> c` and `c -> a`
+  /// are revisited, so a path `x -> a -> c` is only rechecked as its converse
+  /// `c -> a -> x`. The result is therefore only the full fixed point when
+  /// every edge has a converse and `f` respects converses.
   pub fn closure(&mut self, f: impl Fn(I, E, I, E, I) -> Option<E>) {
     let todo = self
       .nodes
       .iter()
       .flat_map(|(a, n)| n.edges.keys().filter(move |&&b| b > a).map(move |&b| (a, b)))
-      .collect();
+      .collect::<Vec<_>>();
+    self.closure_from(todo, f);
+  }
+
+  /// Like [`Graph::closure`], but only starts from the edges between `pairs`,
+  /// for a graph that was closed before those edges changed.
+  pub fn closure_from(
+    &mut self,
+    pairs: impl IntoIterator<Item = (I, I)>,
+    f: impl Fn(I, E, I, E, I) -> Option<E>,
+  ) {
+    let todo = pairs.into_iter().map(|(a, b)| if a < b { (a, b) } else { (b, a) }).collect();
     let mut closure = Closure { graph: self, todo, f };
     while let Some(&(a, b)) = closure.todo.iter().next() {
       closure.todo.remove(&(a, b));
@@ -0,0 +1,102 @@
+use super::{Edge, Graph};
+use crate::idx::Idx;
+
+impl<I: Idx, E: Edge> Graph<I, E> {
+  /// Computes the same fixed point as [`Graph::closure`] by repeatedly
+  /// visiting every path `a -> b -> c` until no edge changes. This is far too
+  /// slow for real use, but simple enough to serve as a reference for testing
+  /// the worklist implementation.
+  pub fn naive_closure(&mut self, f: impl Fn(I, E, I, E, I) -> Option<E>) {
+    loop {
+      let mut changed = false;
+      for a in self.nodes.keys() {
+        let ab = self.nodes[a].edges.iter().map(|(&b, &ab)| (b, ab)).collect::<Vec<_>>();
+        for (b, ab) in ab {
+          let ab = self.get_edge(a, b).unwrap_or(ab);
+          let bc = self.nodes[b].edges.iter().map(|(&c, &bc)| (c, bc)).collect::<Vec<_>>();
+          for (c, bc) in bc {
+            let Some(ac) = f(a, ab, b, bc, c) else { continue };
+            let old = self.get_edge(a, c);
+            self.half_insert(a, c, ac);
+            if self.get_edge(a, c) != old {
+              changed = true;
+              if let Some(ca) = ac.converse() {
+                self.half_insert(c, a, ca);
+              }
+            }
+          }
+        }
+      }
+      if !changed {
+        break;
+      }
+    }
+  }
+}
+
+/// An edge of four atoms whose converse reverses them.
+#[cfg(test)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Symmetric(u8);
+
+#[cfg(test)]
+impl Edge for Symmetric {
+  fn converse(self) -> Option<Self> {
+    Some(Symmetric(self.0.reverse_bits() >> 4))
+  }
+
+  fn merge(self, other: Self) -> Self {
+    Symmetric(self.0 & other.0)
+  }
+}
+
+/// Checks both closures against each other on random graphs, composing edges
+/// with a random table over their atoms. Composition distributes over unions
+/// of atoms and respects converses, so both closures must reach the same fixed
+/// point.
+#[test]
+fn closures_agree() {
+  use crate::rng::Rng;
+
+  let converse = |x: u8| x.reverse_bits() >> 4;
+  for seed in 0..2000 {
+    let mut rng = Rng::new(seed);
+    let mut table = [0; 16];
+    for i in 0..4 {
+      for j in 0..4 {
+        let z = rng.below(16) as u8;
+        table[i * 4 + j] = z;
+        table[(3 - j) * 4 + 3 - i] = converse(z);
+        if 3 - j == i {
+          table[i * 4 + j] = z | converse(z);
+        }
+      }
+    }
+    let compose = |x: u8, y: u8| {
+      let mut z = 0;
+      for i in 0..4 {
+        for j in 0..4 {
+          if x >> i & 1 == 1 && y >> j & 1 == 1 {
+            z |= table[i * 4 + j];
+          }
+        }
+      }
+      z
+    };
+
+    let mut graph = Graph::<usize, Symmetric>::default();
+    let nodes = 2 + rng.below(6);
+    for _ in 0..rng.below(nodes * 2) {
+      let (a, b) = (rng.below(nodes), rng.below(nodes));
+      if a != b {
+        graph.insert(a, b, Symmetric(rng.below(16) as u8));
+      }
+    }
+
+    let f = |a, x: Symmetric, _, y: Symmetric, c| (a != c).then(|| Symmetric(compose(x.0, y.0)));
+    let mut naive = graph.clone();
+    graph.closure(f);
+    naive.naive_closure(f);
+    assert_eq!(graph, naive, "closures differ for seed {seed}");
+  }
+}
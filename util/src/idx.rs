@@ -1,6 +1,7 @@
 use std::{
   fmt::Debug,
   hash::Hash,
+  iter::{Enumerate, Map},
   marker::PhantomData,
   ops::{Index, IndexMut},
 };
@@ -50,6 +51,11 @@ impl<I: Idx, T> IndexVec<I, T> {
     self.vec.len()
   }
 
+  #[inline(always)]
+  pub fn is_empty(&self) -> bool {
+    self.vec.is_empty()
+  }
+
   #[inline(always)]
   pub fn next_index(&self) -> I {
     self.len().into()
@@ -72,11 +78,6 @@ impl<I: Idx, T> IndexVec<I, T> {
     self.vec.get_mut(index.into())
   }
 
-  #[inline(always)]
-  pub fn into_iter(self) -> impl Iterator<Item = (I, T)> {
-    self.vec.into_iter().enumerate().map(map_entry)
-  }
-
   #[inline(always)]
   pub fn iter(&self) -> impl Iterator<Item = (I, &T)> {
     self.vec.iter().enumerate().map(map_entry)
@@ -145,6 +146,16 @@ impl<I: Idx + Debug, T: Debug> Debug for IndexVec<I, T> {
   }
 }
 
+impl<I: Idx, T> IntoIterator for IndexVec<I, T> {
+  type Item = (I, T);
+  type IntoIter = Map<Enumerate<std::vec::IntoIter<T>>, fn((usize, T)) -> (I, T)>;
+
+  #[inline(always)]
+  fn into_iter(self) -> Self::IntoIter {
+    self.vec.into_iter().enumerate().map(map_entry)
+  }
+}
+
 impl<I: Idx, T> From<Vec<T>> for IndexVec<I, T> {
   fn from(vec: Vec<T>) -> Self {
     IndexVec { vec, PhantomData }
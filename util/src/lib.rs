@@ -2,3 +2,4 @@ pub use vine_util::*;
 
 pub mod graph;
 pub mod idx;
+pub mod rng;